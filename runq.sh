@@ -1,2 +1,2 @@
- cargo b &>/dev/null && target/debug/mocka
+ cargo b &>/dev/null && target/debug/mocka "$@"
 
@@ -7,4 +7,7 @@ pub enum Error {
 
     #[error("{0}")]
     MockadocError(#[from] crate::mockadoc::MockadocError),
+
+    #[error("{0}")]
+    Usage(String),
 }
@@ -1,48 +1,152 @@
-use mockagen::run_mockagen;
+use std::path::PathBuf;
 
-use crate::{mockadoc::run_mockadoc, mockagen::{Context, Generator2}};
+use itertools::Itertools;
+use mockagen::run_mockagen_file;
+
+use crate::{error::Error, mockadoc::{run_mockadoc_file, write_document, Settings}, mockagen::{Context, MockagenError}};
 
 mod mockagen;
 mod mockadoc;
 mod error;
 mod utils;
 
-fn mockagen() -> Result<(), crate::error::Error> {
-    let file = std::fs::read_to_string("debug_data/debug.mkg").unwrap();
-    let output = run_mockagen(&file);
-
-    match output {
-        Ok(bindings) => {
-            let mut context: Context = bindings.into();
-            println!("{:?}", context.get_value("region"));
-            println!("{:?}", context.get_value("full-name"));
-        },
-        Err(err) => println!("{}",err),
-    };
-    todo!()
+const USAGE: &str = "\
+usage: mocka <file.mkd> [--rows N] [--rows DOCUMENT=N]... [--seed N] [--out DIR]
+       mocka <file.mkg> <identifier>... [--rows N] [--seed N]";
+
+/// Rows generated by a mockagen run without `--rows`
+const DEFAULT_ROW_COUNT: usize = 10;
+
+struct Args {
+    path: PathBuf,
+    ids: Vec<String>,
+    row_count: Option<usize>,
+    document_row_counts: Vec<(String, usize)>,
+    seed: Option<u64>,
+    out_dir: Option<PathBuf>,
 }
 
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut path = None;
+        let mut ids = vec![];
+        let mut row_count = None;
+        let mut document_row_counts = vec![];
+        let mut seed = None;
+        let mut out_dir = None;
 
-fn mockadoc() -> Result<(), crate::error::Error> {
-    let file = std::fs::read_to_string("debug_data/new-dbg.mkd").unwrap();
-    let output = run_mockadoc(&file);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rows" => {
+                    let value: String = parse_flag(&arg, &mut args)?;
 
-    match output {
-        Ok(()) => { println!("it worked"); }
-        Err(err) => {
-            println!("{err}");
-            // dbg!(err);
+                    // A document's title may itself contain spaces, e.g. `--rows "Customer Account=500"`
+                    match value.split_once('=') {
+                        Some((title, count)) => document_row_counts.push((title.trim().to_owned(), parse_value(&arg, count)?)),
+                        None => row_count = Some(parse_value(&arg, &value)?),
+                    }
+                },
+                "--seed" => seed = Some(parse_flag(&arg, &mut args)?),
+                "--out" => out_dir = Some(parse_flag(&arg, &mut args)?),
+                _ if arg.starts_with("--") => return Err(Error::Usage(format!("unknown option '{arg}'"))),
+                _ if path.is_none() => path = Some(PathBuf::from(arg)),
+                _ => ids.push(arg),
+            }
         }
+
+        let path = path.ok_or_else(|| Error::Usage("missing input file".into()))?;
+
+        Ok(Self { path, ids, row_count, document_row_counts, seed, out_dir })
     }
+}
+
+fn parse_flag<T: std::str::FromStr>(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<T, Error> {
+    let value = args.next()
+        .ok_or_else(|| Error::Usage(format!("missing value for {flag}")))?;
 
-    todo!()
+    parse_value(flag, &value)
 }
 
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, Error> {
+    value.parse()
+        .map_err(|_| Error::Usage(format!("invalid value '{value}' for {flag}")))
+}
+
+fn mockagen(args: &Args) -> Result<(), Error> {
+    let bindings = run_mockagen_file(&args.path)?;
+
+    let mut context = match args.seed {
+        Some(seed) => Context::with_seed(bindings, seed),
+        None => Context::from(bindings),
+    };
+
+    for row in context.generate_rows(&args.ids, args.row_count.unwrap_or(DEFAULT_ROW_COUNT)) {
+        let values = row.map_err(MockagenError::from)?;
+
+        println!("{}", values.iter().join("\t"));
+    }
 
-fn main() -> Result<(), crate::error::Error> {
-    let _ = mockagen()
-        .inspect_err(|e| println!("{e}"));
     Ok(())
+}
 
-    // mockadoc()
+fn mockadoc(args: &Args) -> Result<(), Error> {
+    let settings = Settings {
+        row_count: args.row_count,
+        document_row_counts: args.document_row_counts.clone(),
+        seed: args.seed,
+        out_dir: args.out_dir.clone(),
+    };
+
+    let documents = run_mockadoc_file(&args.path, &settings)?;
+
+    for mut document in documents {
+        let row_count = write_document(&mut document)?;
+
+        println!("wrote {row_count} rows for {}", document.title);
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<(), Error> {
+    let args = Args::parse(std::env::args().skip(1))?;
+
+    match args.path.extension().and_then(|ext| ext.to_str()) {
+        Some("mkg") => mockagen(&args),
+        Some("mkd") => mockadoc(&args),
+        _ => Err(Error::Usage(format!("expected a .mkg or .mkd file, found '{}'", args.path.display()))),
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+
+        if let Error::Usage(_) = err {
+            eprintln!("{USAGE}");
+        }
+
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::Args;
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_args() {
+        let args = parse(&[ "shop.mkd", "--rows", "Customer=5", "--seed", "42" ]).unwrap();
+        assert_eq!(args.document_row_counts, vec![ ("Customer".to_owned(), 5) ]);
+        assert_eq!(args.seed, Some(42));
+
+        assert!(matches!(parse(&[ "shop.mkd", "--row", "5" ]), Err(Error::Usage(message)) if message == "unknown option '--row'"));
+        assert!(matches!(parse(&[ "--seed", "42" ]), Err(Error::Usage(_))));
+    }
 }
@@ -1,194 +1,541 @@
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use rand::SeedableRng;
+
 use crate::{
-    mockadoc::MockadocError,
-    mockadoc::packer::*,
-    mockagen::{run_mockagen, ColumnGenerator, GeneratorSet},
-    utils::error::LanguageError,
+    mockadoc::{
+        evaluator::{events::{compare_timestamps, EventClock, EventSettings}, identities::{IdentityPool, IdentitySettings}, model::{EvaluationError, OutDocument, OutRow}, properties::{resolve_row_counts, DocumentSettings, FileName, FileProperties, RowCount}, references::{reference_order, ForeignKey, Keys}, sql_types::check_sql_types},
+        model::Settings,
+        packer::{model::{Column, ColumnData}, Body, CollectVariant, ColumnNames, Document, DocumentProperties, Documents, Heading, ImportStatement, MockagenId, MockagenIdAndMetadata, MockagenIdentifier, Outputs, Path as ImportPath, PathChars, Properties, RowValue, RowValueCollectedItersByVariant, Schema, Table, Title, Text},
+        MockadocError
+    },
+    mockagen::{run_mockagen_files, Bindings, Context, MockagenError, OutValue, PrimitiveType, Scope, SeededRng},
+    utils::iterator::Transpose
 };
 
-use super::model::{EvaluationError, OutDocument};
-
-fn transpose_table(
-        column_headings: Vec<ColumnHeading>,
-        data_rows: Vec<Vec<CellData>>
-    ) -> Result<Vec<Column>, PackingError>
-{
-    fn into_column<T>(
-        column_number: usize,
-        column: Vec<CellData>,
-        get_props: fn(CellData) -> Option<T>,
-        make_column: impl FnOnce(Vec<T>) -> Column
-    ) -> Result<Column, PackingErrorVariant> {
-
-        let tags_by_row = column.into_iter()
-            .enumerate()
-            .map(|(row, cell)|
-                get_props(cell)
-                    .ok_or_else(|| PackingErrorVariant::InconsistentColumnTypes { column_number, row }))
-            .collect::<Result<Vec<_>, _>>()?;
+fn parse_column(heading: String, cells: Vec<RowValue>) -> Result<Column, EvaluationError> {
+    let variant_iters = cells.into_iter()
+        .collect_variant()
+        .ok_or_else(|| EvaluationError::InconsistentColumn(heading.clone()))?;
+
+    let column_data = match variant_iters {
+        RowValueCollectedItersByVariant::MockagenIdAndMetadata(iter) =>
+            iter.collect::<Result<Vec<_>, _>>()
+                .map(ColumnData::MockagenIdAndMetadata),
+
+        RowValueCollectedItersByVariant::Text(iter) =>
+            iter.map_ok(|t| t.0.trim().to_owned())
+                .collect::<Result<Vec<_>, _>>()
+                .map(ColumnData::Text),
+    };
+
+    let data = column_data
+        .map_err(|_| EvaluationError::InconsistentColumn(heading.clone()))?;
+
+    Ok(Column { heading, data })
+}
+
+fn evaluate_imports(import_statement: ImportStatement, base_dir: &Path) -> Result<Bindings, EvaluationError> {
+    let ImportStatement(imports) = import_statement;
+
+    let paths = imports.into_iter()
+        .map(|ImportPath(PathChars(path))| base_dir.join(path));
+
+    run_mockagen_files(paths)
+        .map_err(EvaluationError::from)
+}
+
+/// Rows per document when neither the command line nor the mockadoc file sets a count
+const DEFAULT_ROW_COUNT: usize = 10;
+
+/// The identifier that the `locale` property is given to, so that generators can depend on it
+const LOCALE_ID: &str = "locale";
+
+/// How a document's rows are generated, as set in the properties block
+struct GenerationSettings {
+    locale: Option<String>,
+    identities: IdentitySettings,
+    events: EventSettings,
+}
+
+/// Where a document's output files are written
+struct Destination {
+    out_dir: PathBuf,
+    file_name: FileName,
+}
 
-        Ok(make_column(tags_by_row))
+/// Finds the column marked `PRIMARY TIMESTAMP`, if any, and checks that it generates dates or timestamps
+fn primary_timestamp(title: &str, ids: &[MockagenIdAndMetadata], bindings: &Bindings) -> Result<Option<(usize, String)>, EvaluationError> {
+    let mut primaries = ids.iter()
+        .enumerate()
+        .filter(|(_, column)| column.is_primary_timestamp());
+
+    let Some((index, MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), ..))) = primaries.next() else {
+        return Ok(None);
+    };
+
+    if primaries.next().is_some() {
+        return Err(EvaluationError::MultiplePrimaryTimestamps(title.to_owned()));
     }
 
-    // Arrange the data into columns
-    let data_columns = data_rows.into_iter()
-        .transpose()
-        .map_err(PackingError::from)?;
+    // Rows can only be ordered by timestamps of a single kind. Unbound identifiers are reported when generating.
+    if let Some(found) = bindings.type_of(id) {
+        let is_timestamp = matches!(found.primitives().collect::<Vec<_>>().as_slice(), [ PrimitiveType::Date | PrimitiveType::DateTime | PrimitiveType::UnixTimestamp ]);
 
-    // Zip the headings with their respective columns
-    column_headings.into_iter()
-        .zip(data_columns.enumerate())
-        .map(|(heading, (column_number, column))| {
+        if !is_timestamp {
+            return Err(EvaluationError::InvalidPrimaryTimestamp { document: title.to_owned(), id: id.to_owned(), found: found.to_string() });
+        }
+    }
 
-            // Possible states of the column, to recognise if it's malformed
-            enum ColumnPacker {
-                Uninitialised,
-                Errored,
-                Valid(ColumnData)
-            }
+    Ok(Some((index, id.to_owned())))
+}
 
-            impl ColumnPacker {
-                fn new() -> Self {
-                    ColumnPacker::Uninitialised
-                }
-
-                // Incrementally assemble the column
-                // accepting each new cell if it's compliant with the column
-                fn append(self, cell_data: CellData) -> Self {
-                    match self {
-                        ColumnPacker::Valid(cell_column) =>
-                            cell_column.append(cell_data)
-                                .map(ColumnPacker::Valid)
-                                .unwrap_or(ColumnPacker::Errored),
-
-                        ColumnPacker::Uninitialised =>
-                            ColumnPacker::Valid(ColumnData::new(cell_data)),
-
-                        ColumnPacker::Errored =>
-                            ColumnPacker::Errored,
-                    }
-                }
-            }
+/// Generates rows for a document from the identifiers in its generator column
+pub struct RowGenerator {
+    ids: Vec<String>,
+    context: Context,
+    start: Scope,
+    identities: Option<IdentityPool>,
+    clock: Option<EventClock>,
+    /// The primary timestamp of the latest event, unless each identity keeps its own
+    last_event: Option<OutValue>,
+    foreign_keys: Vec<ForeignKey>,
+    /// The columns that other documents reference, by their position in `ids`
+    keys: Vec<(usize, Keys)>,
+    remaining: usize,
+    /// The primary timestamp column, when rows have to be generated up front to put them in order
+    sort_by: Option<usize>,
+    sorted: std::vec::IntoIter<OutRow>,
+}
 
-            // This is folding the column, validating each cell in turn and producing
-            let cell_column = column.into_iter()
-                .enumerate()
-                .fold(Ok(ColumnPacker::new()), |cell_column, (row, cell)| {
-                    match cell_column?.append(cell) {
-                        ColumnPacker::Errored =>
-                            Err(PackingErrorVariant::InconsistentColumnTypes { column_number, row }),
-
-                        cell_column =>
-                            Ok(cell_column),
-                    }
-                })?;
-            
-            let ColumnHeading(title) = heading; 
-
-            // Unwrap or throw errors
-            match cell_column {
-                ColumnPacker::Valid(data) => Ok(Column { title, data }),
-                ColumnPacker::Errored => todo!("Produce error for this edgecase (should be impossible as these errors should already be propagated)"),
-                ColumnPacker::Uninitialised => todo!("Produce error complaining that there are no cells in this column")
-            }
+impl RowGenerator {
+    fn new(title: &str, columns: &[Column], bindings: &Bindings, rng: SeededRng, row_count: usize, settings: &GenerationSettings) -> Result<Self, EvaluationError> {
+        let mut generator_columns = columns.iter()
+            .filter_map(Column::mockagen_ids);
+
+        let generator_column = generator_columns.next()
+            .ok_or_else(|| EvaluationError::MissingGeneratorColumn(title.to_owned()))?;
+
+        if generator_columns.next().is_some() {
+            return Err(EvaluationError::MultipleGeneratorColumns(title.to_owned()));
+        }
+
+        let ids = generator_column.iter()
+            .map(|MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), ..)| id.to_owned())
+            .collect();
+
+        let primary_timestamp = primary_timestamp(title, generator_column, bindings)?;
+
+        let foreign_keys = generator_column.iter()
+            .filter_map(|MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), _, reference)|
+                reference.as_ref().map(|reference| ForeignKey::new(id.to_owned(), reference)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // A personal primary timestamp is drawn for each of the identity's events, rather than once per identity
+        let personal_ids = generator_column.iter()
+            .filter(|column| column.is_personal() && !column.is_primary_timestamp())
+            .map(|MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), ..)| id.to_owned())
+            .collect::<Vec<_>>();
+
+        let identities = (!personal_ids.is_empty())
+            .then(|| IdentityPool::new(personal_ids, &settings.identities, row_count));
+
+        // A single stream of arrivals already comes in order, so only other timestamps need sorting
+        let (sort_by, clock) = match primary_timestamp {
+            Some((index, id)) => {
+                let sort_by = (!settings.events.arrivals.in_order()).then_some(index);
+                (sort_by, Some(EventClock::new(id, settings.events)))
+            },
+            None => (None, None),
+        };
+
+        let mut start = Scope::default();
+        if let Some(locale) = &settings.locale {
+            start.replace_value(LOCALE_ID, OutValue::String(locale.clone()));
+        }
+
+        Ok(Self {
+            ids,
+            context: Context::new(bindings.clone(), rng),
+            start,
+            identities,
+            clock,
+            last_event: None,
+            foreign_keys,
+            keys: vec![],
+            remaining: row_count,
+            sort_by,
+            sorted: Vec::new().into_iter(),
         })
-        .collect::<Result<_, _>>()
-        .map_err(PackingError::new)
+    }
+
+    /// Every `PERSONAL` column in a row comes from the same identity, which may also appear in other rows.
+    /// The primary timestamp is generated before the rest of the row, and the locale, if set, before anything else.
+    /// References to other documents are picked for every row, after the identity, so that the rest of the row can
+    /// depend on them without them being kept with the identity.
+    pub fn generate_row(&mut self) -> Result<OutRow, EvaluationError> {
+        let (mut identity, identity_event) = match &mut self.identities {
+            Some(identities) => {
+                let (identity, last_event) = identities.pick(&mut self.context, &self.start)?;
+                (identity.clone(), Some(last_event))
+            },
+            None => (self.start.clone(), None),
+        };
+
+        for foreign_key in &mut self.foreign_keys {
+            let value = foreign_key.pick(self.context.rng())?;
+            identity.replace_value(&foreign_key.id, value);
+        }
+
+        let scope = match &self.clock {
+            Some(clock) => {
+                let last_event = match identity_event {
+                    Some(last_event) if clock.per_identity() => last_event,
+                    _ => &mut self.last_event,
+                };
+
+                clock.tick(&mut self.context, &identity, last_event)?
+            },
+            None => identity,
+        };
+
+        let values = self.context.generate_row_from(&scope, &self.ids)
+            .map_err(MockagenError::from)?;
+
+        for (index, keys) in &self.keys {
+            keys.push((*values[*index]).clone());
+        }
+
+        Ok(OutRow(values.iter().map(|value| (**value).clone()).collect()))
+    }
+
+    /// Where the value of `id` is found in each generated row
+    pub fn position_of(&self, id: &str) -> Option<usize> {
+        self.ids.iter().position(|other| other == id)
+    }
+
+    /// The values generated for `id`, which are recorded from here on so that other documents can reference them
+    fn keys_of(&mut self, id: &str) -> Option<Keys> {
+        let index = self.position_of(id)?;
+
+        if let Some((_, keys)) = self.keys.iter().find(|(other, _)| *other == index) {
+            return Some(keys.clone());
+        }
+
+        let keys = Keys::default();
+        self.keys.push((index, keys.clone()));
+
+        Some(keys)
+    }
+
+    /// Rows read as an event log, in the order that the events happened
+    fn generate_sorted(&mut self, index: usize) -> Result<Vec<OutRow>, EvaluationError> {
+        let mut rows = (0..std::mem::take(&mut self.remaining))
+            .map(|_| self.generate_row())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.sort_by(|OutRow(a), OutRow(b)| compare_timestamps(&a[index], &b[index]));
+        Ok(rows)
+    }
 }
 
-fn evaluate_column(column: Column, generators: &GeneratorSet) -> Result<ColumnGenerator, EvaluationError> {
-    todo!()
-    // match column {
-    //     Column::Generators(gen_ids) =>
-    //         generators
-    //             .make_column_generator(gen_ids)
-    //             .map_err(EvaluationError::from),
-
-    //     Column::Metadata(metadatas) =>
-    //         todo!("not quite sure how to do this yet"),
-
-    //     Column::Text { title, data } => // This shouldn't be part of the data generation
-    //         todo!(),
-        
-    //     Column::DataKey { title, data } =>
-    //         todo!(),
-    // }
+/// Rows are generated as they're read, so that only one is held in memory at a time. The exception is a document with
+/// independently generated primary timestamps, which has to be generated in full before it can be put in order.
+impl Iterator for RowGenerator {
+    type Item = Result<OutRow, EvaluationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(index) = self.sort_by.take() {
+            match self.generate_sorted(index) {
+                Ok(rows) => self.sorted = rows.into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        if let Some(row) = self.sorted.next() {
+            return Some(Ok(row));
+        }
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // Nothing more is generated after an error
+        let row = self.generate_row();
+        self.remaining = match row {
+            Ok(_) => self.remaining - 1,
+            Err(_) => 0,
+        };
+
+        Some(row)
+    }
 }
 
-fn evaluate_document(document: Document, generators: &GeneratorSet) -> Result<OutDocument, EvaluationError> {
-    let Document(title, Schema(Table(heading, rows)), outputs) = document;
+fn evaluate_document(document: Document, index: usize, bindings: &Bindings, rng: SeededRng, row_count: usize, settings: &GenerationSettings, destination: &Destination) -> Result<OutDocument, EvaluationError> {
+    let Document(Title(Text(title)), _, Schema(Table(Heading(ColumnNames(headings), _), rows)), Outputs(outputs)) = document;
+    let title = title.trim().to_owned();
 
-    let columns = transpose_table(column_headings, data_rows)?;
-    
-    let rows = columns.into_iter()
-        .map(|column| evaluate_column(column, generators))
+    // These two statements should ideally be moved into packer.rs as TokenRepackers
+    let cells = rows.into_iter()
+        .map(|r| r.0)
+        .transpose::<Vec<_>>()?
+        .collect::<Vec<_>>();
+
+    if headings.len() != cells.len() {
+        return Err(EvaluationError::ColumnCountMismatch { document: title, headings: headings.len(), columns: cells.len() });
+    }
+
+    let columns = headings.into_iter()
+        .zip(cells)
+        .map(|(h, c)| parse_column(h.0.trim().to_owned(), c))
         .collect::<Result<Vec<_>, _>>()?;
 
+    check_sql_types(&title, &columns, bindings)?;
+    let rows = RowGenerator::new(&title, &columns, bindings, rng, row_count, settings)?;
+    let file_name = destination.file_name.render(&title, index);
 
-    Ok(OutDocument(rows))
+    Ok(OutDocument { title, columns, outputs, rows, out_dir: destination.out_dir.clone(), file_name })
 }
 
-fn evaluate_imports(import_statement: ImportStatement) -> Result<GeneratorSet, MockadocError> {
-    match import_statement {
-        ImportStatement(imports) => {
-            let mut generators = None::<GeneratorSet>;
+/// A document's own row count, given on the command line or under its title, takes precedence over the count for every
+/// document, which again may come from the command line or the properties block
+fn document_row_counts(documents: &[Document], settings: &Settings, file_row_count: Option<usize>) -> Result<Vec<usize>, EvaluationError> {
+    let titles = documents.iter()
+        .map(|Document(Title(Text(title)), ..)| title.trim())
+        .collect::<Vec<_>>();
+
+    if let Some((title, _)) = settings.document_row_counts.iter().find(|(title, _)| !titles.contains(&title.as_str())) {
+        return Err(EvaluationError::UnknownDocument(title.clone()));
+    }
 
-            for path in imports.into_iter() {
-                let Path(path) = path;
-                let file = std::fs::read_to_string(path).map_err(MockadocError::from)?;
-                let gen_set = run_mockagen(&file).map_err(MockadocError::from)?;
+    let counts = documents.iter()
+        .zip(titles)
+        .map(|(Document(_, properties, ..), title)| {
+            let from_settings = settings.document_row_counts.iter()
+                .find(|(other, _)| other == title)
+                .map(|(_, count)| RowCount::Fixed(*count));
 
-                match &mut generators {
-                    Some(gens) => gens.merge(gen_set),
-                    None => generators = Some(gen_set),
-                }
-            }
+            let from_document = match properties {
+                Some(DocumentProperties(properties)) => DocumentSettings::read(properties)?.row_count,
+                None => None,
+            };
+
+            Ok((title.to_owned(), from_settings.or(from_document)))
+        })
+        .collect::<Result<Vec<_>, EvaluationError>>()?;
+
+    resolve_row_counts(&counts, settings.row_count.or(file_row_count).unwrap_or(DEFAULT_ROW_COUNT))
+}
+
+/// Connects every foreign key to the values generated for the column that it references, and orders the documents so
+/// that each one is generated after the documents that it references
+fn link_references(mut documents: Vec<OutDocument>) -> Result<Vec<OutDocument>, EvaluationError> {
+    let titles = documents.iter()
+        .map(|document| document.title.clone())
+        .collect::<Vec<_>>();
+
+    let mut references = vec![ vec![]; documents.len() ];
+
+    for child in 0..documents.len() {
+        for foreign_key in 0..documents[child].rows.foreign_keys.len() {
+            let ForeignKey { id, document, column, .. } = &documents[child].rows.foreign_keys[foreign_key];
+            let (id, document, column) = (id.clone(), document.clone(), column.clone());
+
+            let parent = titles.iter()
+                .position(|title| *title == document)
+                .ok_or_else(|| EvaluationError::UnknownDocument(document.clone()))?;
+
+            let keys = documents[parent].rows.keys_of(&column)
+                .ok_or_else(|| EvaluationError::MissingReferencedColumn { document: titles[child].clone(), id, parent: document, column })?;
 
-            Ok(generators.expect("TODO create error explaining that there are no imports"))
+            documents[child].rows.foreign_keys[foreign_key].keys = keys;
+            references[child].push(parent);
         }
     }
-}
 
-pub fn evaluate_mockadoc(mockadoc_file: Body) -> Result<(), MockadocError> {
-    let Body(_, import_statement, Documents(documents), _) = mockadoc_file;
-    let generators = evaluate_imports(import_statement)?;
+    let mut documents = documents.into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
 
-    let documents = documents
+    Ok(reference_order(&titles, &references)?
         .into_iter()
-        .map(|document| evaluate_document(document, &generators).map_err(MockadocError::from_eval_err))
-        .collect::<Result<Vec<_>, _>>()?;
+        .filter_map(|index| documents[index].take())
+        .collect())
+}
+
+pub fn evaluate_mockadoc(body: Body, base_dir: &Path, settings: &Settings) -> Result<Vec<OutDocument>, MockadocError> {
+    let Body(maybe_properties, import_statement, Documents(documents), _) = body;
+    let properties = maybe_properties
+        .map(|Properties(properties)| properties)
+        .unwrap_or_default();
+
+    let properties = FileProperties::read(&properties)?;
+
+    // The command line takes precedence, and an output directory in the file is relative to the file
+    let seed = settings.seed.or(properties.seed);
+
+    let destination = Destination {
+        out_dir: settings.out_dir.clone()
+            .or_else(|| properties.out_dir.map(|out_dir| base_dir.join(out_dir)))
+            .unwrap_or_else(|| PathBuf::from("output")),
+        file_name: properties.file_name,
+    };
+
+    let row_counts = document_row_counts(&documents, settings, properties.row_count)?;
+
+    let settings = GenerationSettings {
+        locale: properties.locale.map(|locale| locale.0),
+        identities: properties.identities,
+        events: properties.events,
+    };
 
-    Ok(())
+    let bindings = evaluate_imports(import_statement, base_dir)?;
+
+    documents.into_iter()
+        .zip(row_counts)
+        .enumerate()
+        .map(|(index, (document, row_count))| {
+            // Each document draws from its own stream, so that its rows don't depend on the documents before it
+            let mut rng = seed.map(SeededRng::seed_from_u64)
+                .unwrap_or_else(SeededRng::from_entropy);
+            rng.set_stream(index as u64);
+
+            evaluate_document(document, index, &bindings, rng, row_count, &settings, &destination)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(link_references)
+        .map_err(MockadocError::from)
 }
 
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use itertools::Itertools;
+    use rand::SeedableRng;
 
-// fn get_debug_object() -> MockadocFile {
-//     MockadocFile {
-//         import_statement: ImportStatement(vec![ String::from("\"./debug.mkg\"") ]),
-//         documents: vec![
-//             Document {
-//                 title: String::from("Transaction"),
-//                 columns: vec![
-//                     Column::Text {
-//                         title: String::from("Template name"),
-//                         data: vec![ String::from("Timestamp"), String::from("Name") ],
-//                     },
-//                     Column::Text {
-//                         title: String::from("Internal name"),
-//                         data: vec![ String::from("UnixTimestamp"), String::from("ActorName") ],
-//                     },
-//                     Column::Text {
-//                         title: String::from("DSQL Type"),
-//                         data: vec![ String::from("string"), String::from("string") ],
-//                     },
-//                     Column::Generators(vec![ String::from("unix-timestamp"), String::from("full-name") ]),
-//                     Column::Metadata(
-//                         vec![
-//                             MetadataProperties(vec![MetadataProperty::PrimaryTimestamp]),
-//                             MetadataProperties(vec![MetadataProperty::Personal]),
-//                         ],
-//                     ),
-//                 ],
-//             },
-//         ],
-//     }
-// }
+    use crate::{
+        mockadoc::{
+            evaluator::{events::{compare_timestamps, Arrivals, EventSettings}, identities::IdentitySettings, model::{EvaluationError, OutDocument}},
+            packer::{model::{Column, ColumnData}, MetadataProperties, MockagenId, MockagenIdAndMetadata, MockagenIdentifier, OneToOne, Personal, PrimaryTimestamp, Reference, ReferenceCardinality, ReferenceColumn, ReferenceDocument},
+        },
+        mockagen::{run_mockagen, Bindings, SeededRng},
+    };
+
+    use super::{link_references, GenerationSettings, RowGenerator};
+
+    fn column(id: &str, reference: Option<(&str, &str)>) -> MockagenIdAndMetadata {
+        let reference = reference.map(|(document, column)| Reference(ReferenceDocument(document.into()), Some(ReferenceColumn(column.into())), None, None));
+
+        MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id.into())), None, reference)
+    }
+
+    fn document(title: &str, ids: Vec<MockagenIdAndMetadata>, bindings: &Bindings) -> OutDocument {
+        let columns = vec![ Column { heading: "Generator".into(), data: ColumnData::MockagenIdAndMetadata(ids) } ];
+        let settings = GenerationSettings { locale: None, identities: Default::default(), events: Default::default() };
+        let rows = RowGenerator::new(title, &columns, bindings, SeededRng::seed_from_u64(42), 20, &settings).unwrap();
+
+        OutDocument { title: title.into(), columns, outputs: vec![], rows, out_dir: PathBuf::new(), file_name: title.into() }
+    }
+
+    #[test]
+    fn test_references_read_the_named_column() {
+        let bindings = run_mockagen("UNIQUE DEF id = integer 1 1000000\nDEF customer = integer 1 5\nDEF amount = integer 1 100\n").unwrap();
+
+        let order = document("Order", vec![ column("customer", Some(("Customer", "id"))), column("amount", None) ], &bindings);
+        let customer = document("Customer", vec![ column("id", None) ], &bindings);
+
+        let mut documents = link_references(vec![ order, customer ]).unwrap();
+        assert_eq!(documents[0].title, "Customer");
+
+        let ids = documents[0].rows.by_ref()
+            .map(|row| row.unwrap().0[0].to_string())
+            .collect::<Vec<_>>();
+
+        for row in documents[1].rows.by_ref() {
+            assert!(ids.contains(&row.unwrap().0[0].to_string()));
+        }
+
+        let order = document("Order", vec![ column("customer", Some(("Customer", "customer-id"))) ], &bindings);
+        let customer = document("Customer", vec![ column("id", None) ], &bindings);
+
+        let missing = link_references(vec![ order, customer ]);
+        assert!(matches!(missing, Err(EvaluationError::MissingReferencedColumn { column, .. }) if column == "customer-id"));
+    }
+
+    #[test]
+    fn test_references_are_picked_for_every_row_of_an_identity() {
+        let bindings = run_mockagen("UNIQUE DEF id = integer 1 1000000\nDEF name = integer 1 100\n").unwrap();
+
+        let name = MockagenIdAndMetadata(MockagenIdentifier(MockagenId("name".into())), Some(MetadataProperties::Personal(Personal)), None);
+        let reference = Reference(ReferenceDocument("Customer".into()), Some(ReferenceColumn("id".into())), Some(ReferenceCardinality::OneToOne(OneToOne)), None);
+        let customer = MockagenIdAndMetadata(MockagenIdentifier(MockagenId("customer".into())), None, Some(reference));
+
+        let order = document("Order", vec![ name, customer ], &bindings);
+        let customer = document("Customer", vec![ column("id", None) ], &bindings);
+
+        let mut documents = link_references(vec![ order, customer ]).unwrap();
+        documents[0].rows.by_ref().for_each(drop);
+
+        // Twenty orders share two identities, but each of them references a customer of its own
+        let customers = documents[1].rows.by_ref()
+            .map(|row| row.unwrap().0[1].to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(customers.len(), 20);
+        assert!(customers.iter().all_unique());
+    }
+
+    #[test]
+    fn test_personal_columns_come_from_one_identity() {
+        let bindings = run_mockagen("DEF first-name = integer 1 1000000\nDEF last-name = integer 1 1000000\nDEF amount = integer 1 1000000\n").unwrap();
+
+        let personal = |id: &str| MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id.into())), Some(MetadataProperties::Personal(Personal)), None);
+        let ids = vec![ personal("first-name"), personal("last-name"), column("amount", None) ];
+        let columns = vec![ Column { heading: "Generator".into(), data: ColumnData::MockagenIdAndMetadata(ids) } ];
+
+        let settings = GenerationSettings {
+            locale: None,
+            identities: IdentitySettings { size: Some(2), reuse: Default::default() },
+            events: Default::default(),
+        };
+
+        let rows = RowGenerator::new("Payment", &columns, &bindings, SeededRng::seed_from_u64(42), 20, &settings).unwrap()
+            .map(|row| row.unwrap().0.iter().map(ToString::to_string).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // Both names repeat together, while the rest of the row is generated afresh
+        let names = rows.iter().map(|row| (&row[0], &row[1])).unique().collect::<Vec<_>>();
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().map(|(first, _)| first).all_unique());
+        assert!(rows.iter().map(|row| &row[2]).unique().count() > 2);
+    }
+
+    #[test]
+    fn test_each_identitys_events_only_move_forward() {
+        let bindings = run_mockagen("UNIQUE DEF person = integer 1 1000000\nDEF at = timestamp/datetime 2023-01-01T00:00:00Z 2023-12-31T23:59:59Z\n").unwrap();
+
+        let person = MockagenIdAndMetadata(MockagenIdentifier(MockagenId("person".into())), Some(MetadataProperties::Personal(Personal)), None);
+        let at = MockagenIdAndMetadata(MockagenIdentifier(MockagenId("at".into())), Some(MetadataProperties::PrimaryTimestamp(PrimaryTimestamp)), None);
+        let columns = vec![ Column { heading: "Generator".into(), data: ColumnData::MockagenIdAndMetadata(vec![ person, at ]) } ];
+
+        let settings = GenerationSettings {
+            locale: None,
+            identities: IdentitySettings { size: Some(3), reuse: Default::default() },
+            events: EventSettings { arrivals: Arrivals::PerIdentity(86400.0), business_hours: false },
+        };
+
+        let mut rows = RowGenerator::new("Event", &columns, &bindings, SeededRng::seed_from_u64(42), 60, &settings).unwrap();
+
+        // In the order that they were generated, rather than the order that they're written in
+        let events = (0..60)
+            .map(|_| rows.generate_row().unwrap().0)
+            .into_group_map_by(|row| row[0].to_string());
+
+        assert_eq!(events.len(), 3);
+        for timestamps in events.values() {
+            assert!(timestamps.windows(2).all(|pair| compare_timestamps(&pair[0][1], &pair[1][1]).is_le()));
+        }
+    }
+}
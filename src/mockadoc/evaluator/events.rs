@@ -0,0 +1,252 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Timelike, Weekday};
+use itertools::Itertools;
+use rand_distr::{Distribution, Exp};
+
+use crate::{
+    mockadoc::evaluator::model::EvaluationError,
+    mockagen::{Context, MockagenError, OutValue, Scope},
+};
+
+/// How many times a timestamp outside of business hours is generated again before giving up
+const BUSINESS_HOURS_RETRIES: usize = 1000;
+
+const OPENING_HOUR: u32 = 9;
+const CLOSING_HOUR: u32 = 17;
+
+/// How the primary timestamps of consecutive rows relate to one another
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Arrivals {
+    /// Each row's timestamp comes from its generator, and the rows are then sorted
+    #[default]
+    Independent,
+    /// Timestamps form a single stream that starts from the generator, with random gaps averaging this many seconds
+    Poisson(f64),
+    /// Each identity's timestamps form a stream of their own, like `Poisson`, and the rows are then sorted
+    PerIdentity(f64),
+}
+
+impl Arrivals {
+    /// Rows come out of a single stream in order, but otherwise have to be sorted
+    pub fn in_order(&self) -> bool {
+        matches!(self, Self::Poisson(_))
+    }
+}
+
+impl FromStr for Arrivals {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (mean, unit, per_identity) = match value.split_whitespace().collect_vec().as_slice() {
+            [ "independent" ] => return Ok(Self::Independent),
+            [ "poisson", mean, unit ] => (*mean, *unit, false),
+            [ "poisson", mean, unit, "per", "identity" ] => (*mean, *unit, true),
+            _ => return Err(()),
+        };
+
+        let seconds = match unit.trim_end_matches('s') {
+            "second" => 1.0,
+            "minute" => 60.0,
+            "hour" => 3600.0,
+            "day" => 86400.0,
+            _ => return Err(()),
+        };
+
+        mean.parse::<f64>().ok()
+            .filter(|mean| *mean > 0.0)
+            .map(|mean| match per_identity {
+                true => Self::PerIdentity(mean * seconds),
+                false => Self::Poisson(mean * seconds),
+            })
+            .ok_or(())
+    }
+}
+
+/// How a document's primary timestamps are generated, as set in the properties block
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventSettings {
+    pub arrivals: Arrivals,
+    pub business_hours: bool,
+}
+
+fn in_business_hours(value: &OutValue) -> bool {
+    match value {
+        OutValue::NaiveDate(date) => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+        OutValue::DateTime(instant, _) =>
+            !matches!(instant.weekday(), Weekday::Sat | Weekday::Sun)
+            && (OPENING_HOUR..CLOSING_HOUR).contains(&instant.hour()),
+        _ => true,
+    }
+}
+
+/// The next weekday that isn't a weekend
+fn next_weekday(date: NaiveDate) -> NaiveDate {
+    date.iter_days()
+        .find(|date| !matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+        .unwrap_or(date)
+}
+
+/// Moves an instant outside of business hours forward to the next opening time, in the instant's own offset
+fn next_opening(instant: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    let date = match instant.hour() < OPENING_HOUR {
+        true => next_weekday(instant.date_naive()),
+        false => next_weekday(instant.date_naive().succ_opt().unwrap_or(instant.date_naive())),
+    };
+
+    let opening = date.and_time(NaiveTime::from_hms_opt(OPENING_HOUR, 0, 0).unwrap_or_default());
+
+    opening.and_local_timezone(*instant.offset())
+        .single()
+        .unwrap_or(instant)
+}
+
+fn roll_into_business_hours(value: OutValue) -> OutValue {
+    match value {
+        value if in_business_hours(&value) => value,
+        OutValue::NaiveDate(date) => OutValue::NaiveDate(next_weekday(date)),
+        OutValue::DateTime(instant, format) => OutValue::DateTime(next_opening(instant), format),
+        value => value,
+    }
+}
+
+/// Dates move by whole days, so gaps shorter than a day are rounded
+fn advance(value: &OutValue, seconds: f64) -> OutValue {
+    match value {
+        OutValue::NaiveDate(date) => {
+            let days = (seconds / 86400.0).round() as i64;
+            OutValue::NaiveDate(date.checked_add_signed(Duration::days(days)).unwrap_or(*date))
+        },
+        OutValue::DateTime(instant, format) => {
+            let gap = Duration::milliseconds((seconds * 1000.0) as i64);
+            OutValue::DateTime(instant.checked_add_signed(gap).unwrap_or(*instant), *format)
+        },
+        value => value.clone(),
+    }
+}
+
+/// Generates the primary timestamp of each row, before the rest of the row is generated from it
+pub struct EventClock {
+    id: String,
+    settings: EventSettings,
+    gaps: Option<Exp<f64>>,
+}
+
+impl EventClock {
+    pub fn new(id: String, settings: EventSettings) -> Self {
+        let gaps = match settings.arrivals {
+            Arrivals::Independent => None,
+            Arrivals::Poisson(mean) | Arrivals::PerIdentity(mean) => Exp::new(1.0 / mean).ok(),
+        };
+
+        Self { id, settings, gaps }
+    }
+
+    /// Whether each identity's events follow on from that identity's last event, rather than from the document's
+    pub fn per_identity(&self) -> bool {
+        matches!(self.settings.arrivals, Arrivals::PerIdentity(_))
+    }
+
+    /// Generates the timestamp alongside the values already in `scope`
+    fn generate(&self, context: &mut Context, scope: &Scope) -> Result<Scope, EvaluationError> {
+        for _ in 0..BUSINESS_HOURS_RETRIES {
+            let scope = context.generate_scope_from(scope, &[ &self.id ])
+                .map_err(MockagenError::from)?;
+
+            let in_hours = scope.get_value(&self.id)
+                .is_some_and(|value| in_business_hours(&value));
+
+            if in_hours || !self.settings.business_hours {
+                return Ok(scope);
+            }
+        }
+
+        Err(EvaluationError::OutsideBusinessHours { id: self.id.clone(), attempts: BUSINESS_HOURS_RETRIES })
+    }
+
+    /// Adds the next timestamp to a row's starting values, following on from `last`, the latest event in the same
+    /// stream, which is then replaced by this one
+    pub fn tick(&self, context: &mut Context, scope: &Scope, last: &mut Option<OutValue>) -> Result<Scope, EvaluationError> {
+        let (Some(gaps), Some(previous)) = (&self.gaps, &*last) else {
+            let scope = self.generate(context, scope)?;
+            *last = scope.get_value(&self.id).map(|value| (*value).clone());
+
+            return Ok(scope);
+        };
+
+        let mut next = advance(previous, gaps.sample(context.rng()));
+        if self.settings.business_hours {
+            next = roll_into_business_hours(next);
+        }
+
+        let mut scope = scope.clone();
+        scope.replace_value(&self.id, next.clone());
+        *last = Some(next);
+
+        Ok(scope)
+    }
+}
+
+/// Orders primary timestamps, which all come from one generator and so share a type
+pub fn compare_timestamps(a: &OutValue, b: &OutValue) -> Ordering {
+    match (a, b) {
+        (OutValue::DateTime(a, _), OutValue::DateTime(b, _)) => a.cmp(b),
+        (OutValue::NaiveDate(a), OutValue::NaiveDate(b)) => a.cmp(b),
+        (OutValue::I64(a), OutValue::I64(b)) => a.cmp(b),
+        (OutValue::F64(a), OutValue::F64(b)) => a.total_cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::mockagen::{Bindings, Context, OutValue, Scope};
+
+    use super::{advance, compare_timestamps, in_business_hours, roll_into_business_hours, Arrivals, EventClock, EventSettings};
+
+    #[test]
+    fn test_arrivals() {
+        assert_eq!("independent".parse(), Ok(Arrivals::Independent));
+        assert_eq!("poisson 5 minutes".parse(), Ok(Arrivals::Poisson(300.0)));
+        assert_eq!("poisson 1 day".parse(), Ok(Arrivals::Poisson(86400.0)));
+        assert_eq!("poisson 0 seconds".parse::<Arrivals>(), Err(()));
+        assert_eq!("poisson 5".parse::<Arrivals>(), Err(()));
+        assert_eq!("poisson 2 days per identity".parse(), Ok(Arrivals::PerIdentity(172800.0)));
+        assert_eq!("poisson 2 days per person".parse::<Arrivals>(), Err(()));
+    }
+
+    #[test]
+    fn test_business_hours() {
+        let instant = |text| OutValue::DateTime(DateTime::parse_from_rfc3339(text).unwrap(), Default::default());
+
+        // 2023-05-12 is a Friday
+        assert!(in_business_hours(&instant("2023-05-12T09:00:00+01:00")));
+        assert!(!in_business_hours(&instant("2023-05-12T17:00:00+01:00")));
+        assert!(!in_business_hours(&instant("2023-05-13T12:00:00+01:00")));
+
+        assert_eq!(roll_into_business_hours(instant("2023-05-12T18:30:00+01:00")).to_string(), "2023-05-15T09:00:00+01:00");
+        assert_eq!(roll_into_business_hours(instant("2023-05-12T06:30:00+01:00")).to_string(), "2023-05-12T09:00:00+01:00");
+        assert_eq!(advance(&instant("2023-05-12T09:00:00Z"), 90.0).to_string(), "2023-05-12T09:01:30Z");
+    }
+
+    #[test]
+    fn test_poisson_arrivals_only_move_forward() {
+        let mut context = Context::with_seed(Bindings::default(), 42);
+        let clock = EventClock::new("at".into(), EventSettings { arrivals: Arrivals::Poisson(3600.0), business_hours: true });
+
+        // The first timestamp would normally come from the generator
+        let mut start = Scope::default();
+        start.replace_value("at", OutValue::DateTime(DateTime::parse_from_rfc3339("2023-05-12T16:30:00Z").unwrap(), Default::default()));
+
+        let mut last = None;
+        let timestamps = (0..50)
+            .map(|_| clock.tick(&mut context, &start, &mut last).unwrap().get_value("at").unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(timestamps.windows(2).all(|pair| compare_timestamps(&pair[0], &pair[1]).is_le()));
+        assert!(timestamps[1..].iter().all(|timestamp| in_business_hours(timestamp)));
+        assert!(compare_timestamps(&timestamps[0], &timestamps[49]).is_lt());
+    }
+}
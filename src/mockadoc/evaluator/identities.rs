@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+use itertools::Itertools;
+use rand::Rng;
+use rand_distr::{Distribution, Zipf};
+
+use crate::mockagen::{Context, MockagenError, OutValue, Scope, SeededRng};
+
+/// Each identity appears in this many rows on average, unless the pool size is set
+const ROWS_PER_IDENTITY: usize = 10;
+
+/// How often each identity in a pool is picked
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Reuse {
+    /// Every identity is as likely as any other
+    #[default]
+    Uniform,
+    /// The first identities are picked far more often than the rest, more so with a larger exponent
+    Zipf(f64),
+}
+
+impl FromStr for Reuse {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_whitespace().collect_vec().as_slice() {
+            [ "uniform" ] => Ok(Self::Uniform),
+            [ "zipf" ] => Ok(Self::Zipf(1.0)),
+            [ "zipf", exponent ] => exponent.parse().ok()
+                .filter(|exponent| *exponent >= 0.0)
+                .map(Self::Zipf)
+                .ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How a document's identities are drawn, as set in the properties block
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentitySettings {
+    pub size: Option<usize>,
+    pub reuse: Reuse,
+}
+
+/// The identities that a document's `PERSONAL` columns are drawn from. An identity holds the values of every personal
+/// column, along with the values that they were generated from, so the same person keeps the same details in every row.
+pub struct IdentityPool {
+    personal_ids: Vec<String>,
+    identities: Vec<Option<Scope>>,
+    /// The primary timestamp of each identity's latest event, for identities whose events form a stream of their own
+    last_events: Vec<Option<OutValue>>,
+    zipf: Option<Zipf<f64>>,
+}
+
+impl IdentityPool {
+    pub fn new(personal_ids: Vec<String>, settings: &IdentitySettings, row_count: usize) -> Self {
+        let size = settings.size
+            .unwrap_or(row_count / ROWS_PER_IDENTITY)
+            .max(1);
+
+        let zipf = match settings.reuse {
+            Reuse::Uniform => None,
+            Reuse::Zipf(exponent) => Zipf::new(size as u64, exponent).ok(),
+        };
+
+        Self { personal_ids, identities: vec![ None; size ], last_events: vec![ None; size ], zipf }
+    }
+
+    fn pick_index(&self, rng: &mut SeededRng) -> usize {
+        match &self.zipf {
+            Some(zipf) => zipf.sample(rng) as usize - 1,
+            None => rng.gen_range(0..self.identities.len()),
+        }
+    }
+
+    /// Picks an identity for the next row, generating it from `start` the first time that it's picked. The identity's
+    /// latest event comes with it, so that the row's event can take its place.
+    pub fn pick(&mut self, context: &mut Context, start: &Scope) -> Result<(&Scope, &mut Option<OutValue>), MockagenError> {
+        let index = self.pick_index(context.rng());
+
+        let identity = match &mut self.identities[index] {
+            Some(identity) => identity,
+            slot @ None => slot.insert(context.generate_scope_from(start, &self.personal_ids)?),
+        };
+
+        Ok((identity, &mut self.last_events[index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::mockagen::SeededRng;
+
+    use super::{IdentityPool, IdentitySettings, Reuse};
+
+    #[test]
+    fn test_reuse() {
+        assert_eq!("uniform".parse(), Ok(Reuse::Uniform));
+        assert_eq!("zipf 1.5".parse(), Ok(Reuse::Zipf(1.5)));
+        assert_eq!("zipf".parse(), Ok(Reuse::Zipf(1.0)));
+        assert_eq!("zipf -1".parse::<Reuse>(), Err(()));
+        assert_eq!("normal".parse::<Reuse>(), Err(()));
+
+        let mut rng = SeededRng::seed_from_u64(42);
+        let pool = IdentityPool::new(vec![], &IdentitySettings { size: None, reuse: Reuse::Zipf(2.0) }, 1000);
+        let picks = (0..1000).map(|_| pool.pick_index(&mut rng)).collect::<Vec<_>>();
+
+        assert!(picks.iter().all(|index| *index < 100));
+        assert!(picks.iter().filter(|index| **index == 0).count() > 500);
+    }
+}
@@ -1,6 +1,9 @@
-// mod evaluator;
+mod evaluator;
+mod events;
+mod identities;
+mod properties;
+mod references;
+mod sql_types;
 pub mod model;
-// pub mod evaluator2;
-// mod evaluator;
 
-// pub use evaluator::evaluate_mockadoc;
+pub use evaluator::evaluate_mockadoc;
@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 use crate::{
+    mockadoc::{evaluator::evaluator::RowGenerator, packer::{model::Column, OutputType}},
     mockagen::{MockagenError, OutValue},
     utils::iterator::TransposeError
 };
@@ -16,22 +19,77 @@ pub enum EvaluationError {
 
     #[error("malformed table")]
     TableShapeError(#[from] TransposeError),
+
+    #[error("document '{document}' has {headings} column headings but {columns} columns")]
+    ColumnCountMismatch { document: String, headings: usize, columns: usize },
+
+    #[error("column '{0}' mixes generators and text")]
+    InconsistentColumn(String),
+
+    #[error("document '{0}' has no generator column")]
+    MissingGeneratorColumn(String),
+
+    #[error("document '{0}' has more than one generator column")]
+    MultipleGeneratorColumns(String),
+
+    #[error("'{id}' generates {found} values, which can't be stored as '{sql_type}' in document '{document}'")]
+    SqlTypeMismatch { document: String, id: String, sql_type: String, found: String },
+
+    #[error("document '{0}' has more than one primary timestamp")]
+    MultiplePrimaryTimestamps(String),
+
+    #[error("primary timestamp '{id}' in document '{document}' generates {found} values rather than dates or timestamps")]
+    InvalidPrimaryTimestamp { document: String, id: String, found: String },
+
+    #[error("'{id}' produced no timestamps within business hours in {attempts} attempts")]
+    OutsideBusinessHours { id: String, attempts: usize },
+
+    #[error("invalid value '{value}' for property '{key}'")]
+    InvalidProperty { key: String, value: String },
+
+    #[error("unknown property '{0}'")]
+    UnknownProperty(String),
+
+    #[error("no document is titled '{0}'")]
+    UnknownDocument(String),
+
+    #[error("document row counts depend on one another: {0}")]
+    RowCountCycle(String),
+
+    #[error("'{id}' in document '{document}' references '{parent}', which has no '{column}' column")]
+    MissingReferencedColumn { document: String, id: String, parent: String, column: String },
+
+    #[error("documents reference one another: {0}")]
+    ReferenceCycle(String),
+
+    #[error("invalid optional percentage '{found}%' for '{id}'")]
+    InvalidNullChance { id: String, found: String },
+
+    #[error("ran out of '{id}' values from document '{document}' to reference")]
+    ReferencesExhausted { id: String, document: String },
 }
 
-// pub struct OutRow(pub Vec<OutValue>);
+#[derive(Debug)]
+pub struct OutRow(pub Vec<OutValue>);
 
-// pub struct OutDocument(pub Vec<()>);
+pub struct OutDocument {
+    pub title: String,
+    pub columns: Vec<Column>,
+    pub outputs: Vec<OutputType>,
+    /// Generates the rows as they're read
+    pub rows: RowGenerator,
+    pub out_dir: PathBuf,
+    pub file_name: String,
+}
 
-// impl OutDocument<'_> {
-//     pub fn generate(&self) -> Result<Vec<OutRow>, EvaluationError> {
-//         let output = self.0.iter()
-//             .map(|col_gen| col_gen.generate_column())
-//             .collect::<Result<Vec<_>, _>>().map_err(EvaluationError::from)?
-//             .into_iter()
-//             .transpose().map_err(EvaluationError::TableShapeError)?
-//             .map(OutRow)
-//             .collect();
+impl OutDocument {
+    pub fn column(&self, heading: &str) -> Option<&Column> {
+        self.columns.iter()
+            .find(|column| column.heading == heading.trim())
+    }
 
-//         Ok(output)
-//     }
-// }
+    /// Where the output with the given extension is written
+    pub fn output_path(&self, extension: &str) -> PathBuf {
+        self.out_dir.join(format!("{}.{}", self.file_name, extension))
+    }
+}
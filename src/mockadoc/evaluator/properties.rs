@@ -0,0 +1,300 @@
+use std::{path::PathBuf, str::FromStr};
+
+use itertools::Itertools;
+
+use crate::mockadoc::{
+    evaluator::{events::EventSettings, identities::IdentitySettings, model::EvaluationError},
+    packer::{Property, PropertyKey, PropertyValue},
+};
+
+/// Every key that may appear in a properties block
+const KNOWN_KEYS: [&str; 9] = [
+    "rows", "seed", "output-dir", "file-name", "locale",
+    "identities", "identity-reuse", "arrivals", "business-hours",
+];
+
+/// Every key that may appear between a document's title and its schema
+const DOCUMENT_KEYS: [&str; 1] = [ "rows" ];
+
+/// How many rows a document has, either outright or relative to another document, e.g. `10 per Customer`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowCount {
+    Fixed(usize),
+    PerDocument(f64, String),
+}
+
+impl FromStr for RowCount {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let Some((ratio, document)) = value.split_once(" per ") else {
+            return value.parse().map(Self::Fixed).map_err(|_| ());
+        };
+
+        ratio.trim().parse::<f64>().ok()
+            .filter(|ratio| ratio.is_finite() && *ratio >= 0.0 && !document.trim().is_empty())
+            .map(|ratio| Self::PerDocument(ratio, document.trim().to_owned()))
+            .ok_or(())
+    }
+}
+
+/// A part of a file name template
+#[derive(Debug, Clone, PartialEq)]
+enum FileNamePart {
+    Text(String),
+    Title,
+    Index,
+}
+
+/// The name given to each document's output files, before the extension, e.g. `{index}-{title}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileName(Vec<FileNamePart>);
+
+impl Default for FileName {
+    fn default() -> Self {
+        Self(vec![ FileNamePart::Title ])
+    }
+}
+
+impl FromStr for FileName {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut rest = value;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or(())? + start;
+
+            if start > 0 {
+                parts.push(FileNamePart::Text(rest[..start].to_owned()));
+            }
+
+            parts.push(match &rest[start + 1..end] {
+                "title" => FileNamePart::Title,
+                "index" => FileNamePart::Index,
+                _ => return Err(()),
+            });
+
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(FileNamePart::Text(rest.to_owned()));
+        }
+
+        match parts.is_empty() || rest.contains('}') {
+            true => Err(()),
+            false => Ok(Self(parts)),
+        }
+    }
+}
+
+impl FileName {
+    /// Documents are numbered from 1 in the order that they appear in the file
+    pub fn render(&self, title: &str, index: usize) -> String {
+        self.0.iter()
+            .map(|part| match part {
+                FileNamePart::Text(text) => text.clone(),
+                FileNamePart::Title => title.to_owned(),
+                FileNamePart::Index => (index + 1).to_string(),
+            })
+            .collect()
+    }
+}
+
+/// A language tag such as `en` or `en-GB`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale(pub String);
+
+impl FromStr for Locale {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut subtags = value.split(['-', '_']);
+
+        let is_language = subtags.next()
+            .is_some_and(|language| (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()));
+
+        let is_valid = is_language
+            && subtags.all(|subtag| (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        match is_valid {
+            true => Ok(Self(value.to_owned())),
+            false => Err(()),
+        }
+    }
+}
+
+fn check_keys(properties: &[Property], known: &[&str]) -> Result<(), EvaluationError> {
+    let unknown = properties.iter()
+        .find(|Property(PropertyKey(key), _)| !known.contains(&key.as_str()));
+
+    match unknown {
+        Some(Property(PropertyKey(key), _)) => Err(EvaluationError::UnknownProperty(key.to_owned())),
+        None => Ok(()),
+    }
+}
+
+fn read_property<T: FromStr>(properties: &[Property], name: &str) -> Result<Option<T>, EvaluationError> {
+    properties.iter()
+        .find(|Property(PropertyKey(key), _)| key == name)
+        .map(|Property(PropertyKey(key), PropertyValue(value))|
+            value.trim().parse()
+                .map_err(|_| EvaluationError::InvalidProperty { key: key.to_owned(), value: value.to_owned() }))
+        .transpose()
+}
+
+fn read_identity_settings(properties: &[Property]) -> Result<IdentitySettings, EvaluationError> {
+    let size = read_property(properties, "identities")?;
+
+    if size == Some(0) {
+        return Err(EvaluationError::InvalidProperty { key: "identities".into(), value: "0".into() });
+    }
+
+    Ok(IdentitySettings { size, reuse: read_property(properties, "identity-reuse")?.unwrap_or_default() })
+}
+
+fn read_event_settings(properties: &[Property]) -> Result<EventSettings, EvaluationError> {
+    Ok(EventSettings {
+        arrivals: read_property(properties, "arrivals")?.unwrap_or_default(),
+        business_hours: read_property(properties, "business-hours")?.unwrap_or_default(),
+    })
+}
+
+/// The settings in a mockadoc file's properties block. Anything that can also be given on the command line is
+/// optional here, so that the command line can take precedence.
+#[derive(Debug, Default)]
+pub struct FileProperties {
+    pub row_count: Option<usize>,
+    pub seed: Option<u64>,
+    pub out_dir: Option<PathBuf>,
+    pub file_name: FileName,
+    pub locale: Option<Locale>,
+    pub identities: IdentitySettings,
+    pub events: EventSettings,
+}
+
+impl FileProperties {
+    pub fn read(properties: &[Property]) -> Result<Self, EvaluationError> {
+        check_keys(properties, &KNOWN_KEYS)?;
+
+        Ok(Self {
+            row_count: read_property(properties, "rows")?,
+            seed: read_property(properties, "seed")?,
+            out_dir: read_property(properties, "output-dir")?,
+            file_name: read_property(properties, "file-name")?.unwrap_or_default(),
+            locale: read_property(properties, "locale")?,
+            identities: read_identity_settings(properties)?,
+            events: read_event_settings(properties)?,
+        })
+    }
+}
+
+/// The settings given between a document's title and its schema
+#[derive(Debug, Default)]
+pub struct DocumentSettings {
+    pub row_count: Option<RowCount>,
+}
+
+impl DocumentSettings {
+    pub fn read(properties: &[Property]) -> Result<Self, EvaluationError> {
+        check_keys(properties, &DOCUMENT_KEYS)?;
+
+        Ok(Self { row_count: read_property(properties, "rows")? })
+    }
+}
+
+/// Works out the row count of every document, following relative counts through to the documents that they're
+/// relative to. Documents without a count of their own use `default`.
+pub fn resolve_row_counts(counts: &[(String, Option<RowCount>)], default: usize) -> Result<Vec<usize>, EvaluationError> {
+    fn resolve(index: usize, counts: &[(String, Option<RowCount>)], default: usize, path: &mut Vec<usize>, resolved: &mut [Option<usize>]) -> Result<usize, EvaluationError> {
+        if let Some(count) = resolved[index] {
+            return Ok(count);
+        }
+
+        if path.contains(&index) {
+            let cycle = path.iter()
+                .skip_while(|step| **step != index)
+                .chain([ &index ])
+                .map(|step| counts[*step].0.as_str())
+                .join(" -> ");
+
+            return Err(EvaluationError::RowCountCycle(cycle));
+        }
+
+        let count = match &counts[index].1 {
+            None => default,
+            Some(RowCount::Fixed(count)) => *count,
+            Some(RowCount::PerDocument(ratio, document)) => {
+                let parent = counts.iter()
+                    .position(|(title, _)| title == document)
+                    .ok_or_else(|| EvaluationError::UnknownDocument(document.clone()))?;
+
+                path.push(index);
+                let parent_count = resolve(parent, counts, default, path, resolved)?;
+                path.pop();
+
+                (ratio * parent_count as f64).round() as usize
+            },
+        };
+
+        resolved[index] = Some(count);
+        Ok(count)
+    }
+
+    let mut resolved = vec![ None; counts.len() ];
+
+    (0..counts.len())
+        .map(|index| resolve(index, counts, default, &mut vec![], &mut resolved))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockadoc::{evaluator::model::EvaluationError, packer::{Property, PropertyKey, PropertyValue}};
+
+    use super::{resolve_row_counts, FileName, FileProperties, Locale, RowCount};
+
+    #[test]
+    fn test_properties() {
+        let property = |key: &str, value: &str| Property(PropertyKey(key.into()), PropertyValue(value.into()));
+
+        let properties = FileProperties::read(&[ property("rows", "250"), property("file-name", "{index}-{title}.export"), property("locale", "en-GB") ]).unwrap();
+        assert_eq!(properties.row_count, Some(250));
+        assert_eq!(properties.file_name.render("Transaction", 0), "1-Transaction.export");
+        assert_eq!(properties.locale, Some(Locale("en-GB".into())));
+
+        assert!(matches!(FileProperties::read(&[ property("row", "250") ]), Err(EvaluationError::UnknownProperty(key)) if key == "row"));
+        assert!(matches!(FileProperties::read(&[ property("Seed", "1") ]), Err(EvaluationError::UnknownProperty(key)) if key == "Seed"));
+        assert!(matches!(FileProperties::read(&[ property("rows", "many") ]), Err(EvaluationError::InvalidProperty { .. })));
+
+        assert!("{name}".parse::<FileName>().is_err());
+        assert!("{title".parse::<FileName>().is_err());
+        assert!("english".parse::<Locale>().is_err());
+
+        assert_eq!("500".parse(), Ok(RowCount::Fixed(500)));
+        assert_eq!("2.5 per Customer Account".parse(), Ok(RowCount::PerDocument(2.5, "Customer Account".into())));
+        assert!("10 per".parse::<RowCount>().is_err());
+    }
+
+    #[test]
+    fn test_relative_row_counts() {
+        let counts = [
+            ("Channel".to_owned(), Some(RowCount::PerDocument(10.0, "Customer".into()))),
+            ("Customer".to_owned(), Some(RowCount::Fixed(25))),
+            ("Event".to_owned(), Some(RowCount::PerDocument(0.5, "Channel".into()))),
+            ("Note".to_owned(), None),
+        ];
+
+        assert_eq!(resolve_row_counts(&counts, 10).unwrap(), vec![ 250, 25, 125, 10 ]);
+
+        let cycle = [
+            ("A".to_owned(), Some(RowCount::PerDocument(1.0, "B".into()))),
+            ("B".to_owned(), Some(RowCount::PerDocument(1.0, "A".into()))),
+        ];
+
+        assert!(matches!(resolve_row_counts(&cycle, 10), Err(EvaluationError::RowCountCycle(path)) if path == "A -> B -> A"));
+        assert!(matches!(resolve_row_counts(&counts[..1], 10), Err(EvaluationError::UnknownDocument(title)) if title == "Customer"));
+    }
+}
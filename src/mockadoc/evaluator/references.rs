@@ -0,0 +1,190 @@
+use std::{cell::RefCell, rc::Rc};
+
+use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    mockadoc::{
+        evaluator::model::EvaluationError,
+        packer::{Reference, ReferenceCardinality, ReferenceColumn, ReferenceDocument, ReferenceOptional, ReferencePercentage},
+    },
+    mockagen::{OutValue, SeededRng},
+};
+
+/// How many rows of the referencing document may share a row of the referenced document
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Cardinality {
+    /// Any number of rows may reference the same row
+    #[default]
+    OneToMany,
+    /// Each row is referenced at most once
+    OneToOne,
+}
+
+/// The values of a column as they're generated, shared between the document that generates them and the documents
+/// that reference them
+#[derive(Debug, Clone, Default)]
+pub struct Keys(Rc<RefCell<Vec<OutValue>>>);
+
+impl Keys {
+    pub fn push(&self, value: OutValue) {
+        self.0.borrow_mut().push(value);
+    }
+}
+
+/// A column whose values are drawn from the rows generated for another document
+pub struct ForeignKey {
+    pub id: String,
+    pub document: String,
+    /// The referenced document's column that values are drawn from
+    pub column: String,
+    pub keys: Keys,
+    cardinality: Cardinality,
+    null_chance: f64,
+    /// The keys that haven't been referenced yet, in a random order, for one to one references
+    unused: Option<Vec<usize>>,
+}
+
+impl ForeignKey {
+    pub fn new(id: String, reference: &Reference) -> Result<Self, EvaluationError> {
+        let Reference(ReferenceDocument(document), column, cardinality, optional) = reference;
+
+        let column = column.as_ref()
+            .map(|ReferenceColumn(column)| column.to_owned())
+            .unwrap_or_else(|| id.clone());
+
+        let cardinality = match cardinality {
+            Some(ReferenceCardinality::OneToOne(_)) => Cardinality::OneToOne,
+            Some(ReferenceCardinality::OneToMany(_)) | None => Cardinality::OneToMany,
+        };
+
+        let null_chance = match optional {
+            Some(ReferenceOptional(ReferencePercentage(percentage))) => percentage.parse::<f64>().ok()
+                .filter(|percentage| *percentage <= 100.0)
+                .map(|percentage| percentage / 100.0)
+                .ok_or_else(|| EvaluationError::InvalidNullChance { id: id.clone(), found: percentage.clone() })?,
+            None => 0.0,
+        };
+
+        Ok(Self { id, document: document.trim().to_owned(), column, keys: Keys::default(), cardinality, null_chance, unused: None })
+    }
+
+    /// Picks a value from the referenced document's rows, which have to have been generated already
+    pub fn pick(&mut self, rng: &mut SeededRng) -> Result<OutValue, EvaluationError> {
+        if rng.gen_bool(self.null_chance) {
+            return Ok(OutValue::Null);
+        }
+
+        let keys = self.keys.0.borrow();
+
+        let index = match self.cardinality {
+            Cardinality::OneToMany => (!keys.is_empty()).then(|| rng.gen_range(0..keys.len())),
+            Cardinality::OneToOne => self.unused
+                .get_or_insert_with(|| {
+                    let mut unused = (0..keys.len()).collect::<Vec<_>>();
+                    unused.shuffle(rng);
+                    unused
+                })
+                .pop(),
+        };
+
+        index.map(|index| keys[index].clone())
+            .ok_or_else(|| EvaluationError::ReferencesExhausted { id: self.id.clone(), document: self.document.clone() })
+    }
+}
+
+/// Orders documents so that every document comes after the documents that it references. `references` holds the
+/// indexes of the documents that each document references.
+pub fn reference_order(titles: &[String], references: &[Vec<usize>]) -> Result<Vec<usize>, EvaluationError> {
+    fn visit(index: usize, titles: &[String], references: &[Vec<usize>], path: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<(), EvaluationError> {
+        if order.contains(&index) {
+            return Ok(());
+        }
+
+        if let Some(start) = path.iter().position(|step| *step == index) {
+            let cycle = path[start..].iter()
+                .chain([ &index ])
+                .map(|step| titles[*step].as_str())
+                .join(" -> ");
+
+            return Err(EvaluationError::ReferenceCycle(cycle));
+        }
+
+        path.push(index);
+        for parent in &references[index] {
+            visit(*parent, titles, references, path, order)?;
+        }
+        path.pop();
+
+        order.push(index);
+        Ok(())
+    }
+
+    let mut order = vec![];
+
+    for index in 0..titles.len() {
+        visit(index, titles, references, &mut vec![], &mut order)?;
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::{
+        mockadoc::{evaluator::model::EvaluationError, packer::{OneToOne, Reference, ReferenceCardinality, ReferenceColumn, ReferenceDocument, ReferenceOptional, ReferencePercentage}},
+        mockagen::{OutValue, SeededRng},
+    };
+
+    use super::{reference_order, ForeignKey};
+
+    #[test]
+    fn test_one_to_one_references() {
+        let mut rng = SeededRng::seed_from_u64(42);
+        let reference = Reference(ReferenceDocument("Customer".into()), None, Some(ReferenceCardinality::OneToOne(OneToOne)), None);
+        let mut foreign_key = ForeignKey::new("customer-id".into(), &reference).unwrap();
+        assert_eq!(foreign_key.column, "customer-id");
+
+        let named = Reference(ReferenceDocument("Customer".into()), Some(ReferenceColumn("id".into())), None, None);
+        assert_eq!(ForeignKey::new("customer-id".into(), &named).unwrap().column, "id");
+
+        for id in 0..5 {
+            foreign_key.keys.push(OutValue::I64(id));
+        }
+
+        let mut picks = (0..5)
+            .map(|_| match foreign_key.pick(&mut rng).unwrap() {
+                OutValue::I64(id) => id,
+                value => panic!("unexpected value {value}"),
+            })
+            .collect::<Vec<_>>();
+
+        picks.sort();
+        assert_eq!(picks, vec![ 0, 1, 2, 3, 4 ]);
+        assert!(matches!(foreign_key.pick(&mut rng), Err(EvaluationError::ReferencesExhausted { .. })));
+    }
+
+    #[test]
+    fn test_optional_references() {
+        let mut rng = SeededRng::seed_from_u64(42);
+        let reference = Reference(ReferenceDocument("Customer".into()), None, None, Some(ReferenceOptional(ReferencePercentage("25".into()))));
+        let mut foreign_key = ForeignKey::new("customer-id".into(), &reference).unwrap();
+        foreign_key.keys.push(OutValue::I64(1));
+
+        let nulls = (0..1000)
+            .filter(|_| matches!(foreign_key.pick(&mut rng).unwrap(), OutValue::Null))
+            .count();
+
+        assert!((200..300).contains(&nulls));
+    }
+
+    #[test]
+    fn test_reference_order() {
+        let titles = [ "Order".to_owned(), "Customer".to_owned(), "Region".to_owned() ];
+
+        assert_eq!(reference_order(&titles, &[ vec![ 1 ], vec![ 2 ], vec![] ]).unwrap(), vec![ 2, 1, 0 ]);
+        assert!(matches!(reference_order(&titles, &[ vec![ 1 ], vec![ 0 ], vec![] ]), Err(EvaluationError::ReferenceCycle(cycle)) if cycle == "Order -> Customer -> Order"));
+    }
+}
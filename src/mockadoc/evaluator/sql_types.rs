@@ -0,0 +1,85 @@
+use crate::{
+    mockadoc::{
+        evaluator::model::EvaluationError,
+        packer::{model::Column, MockagenId, MockagenIdAndMetadata, MockagenIdentifier},
+    },
+    mockagen::{Bindings, PrimitiveType},
+};
+
+/// The headings of a schema's SQL type column, compared without regard to case
+const SQL_TYPE_HEADINGS: &[&str] = &[ "SQL Type", "DSQL Type" ];
+
+/// The types of generated value that can be stored in a column of the given SQL type,
+/// or `None` if the type isn't recognised. Lengths such as `VARCHAR(255)` are ignored.
+fn storable_types(sql_type: &str) -> Option<&'static [PrimitiveType]> {
+    let name = sql_type.split('(')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    let types: &[PrimitiveType] = match name.as_str() {
+        "string" | "text" | "varchar" | "char" =>
+            &[ PrimitiveType::String, PrimitiveType::Date, PrimitiveType::DateTime, PrimitiveType::UnixTimestamp ],
+
+        "integer" | "int" | "bigint" | "smallint" =>
+            &[ PrimitiveType::Integer, PrimitiveType::UnixTimestamp ],
+
+        "real" | "float" | "double" | "decimal" | "numeric" =>
+            &[ PrimitiveType::Real, PrimitiveType::Integer ],
+
+        "date" => &[ PrimitiveType::Date ],
+        "timestamp" | "datetime" => &[ PrimitiveType::DateTime, PrimitiveType::UnixTimestamp ],
+        "json" | "array" => &[ PrimitiveType::List ],
+        _ => return None,
+    };
+
+    Some(types)
+}
+
+/// Checks the SQL type given for each row of the schema against the type inferred for its generator.
+/// Types that aren't recognised, such as those specific to one database, are left unchecked.
+pub fn check_sql_types(title: &str, columns: &[Column], bindings: &Bindings) -> Result<(), EvaluationError> {
+    let Some(sql_types) = columns.iter()
+        .find(|column| SQL_TYPE_HEADINGS.iter().any(|heading| column.heading.eq_ignore_ascii_case(heading)))
+        .and_then(Column::texts)
+    else {
+        return Ok(());
+    };
+
+    let Some(ids) = columns.iter().find_map(Column::mockagen_ids) else {
+        return Ok(());
+    };
+
+    for (sql_type, MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), ..)) in sql_types.iter().zip(ids) {
+        let (Some(storable), Some(found)) = (storable_types(sql_type), bindings.type_of(id)) else {
+            continue;
+        };
+
+        if found.primitives().any(|primitive| !storable.contains(&primitive)) {
+            return Err(EvaluationError::SqlTypeMismatch {
+                document: title.to_owned(),
+                id: id.to_owned(),
+                sql_type: sql_type.to_owned(),
+                found: found.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockagen::PrimitiveType;
+
+    use super::storable_types;
+
+    #[test]
+    fn test_storable_types() {
+        assert!(storable_types("VARCHAR(255)").is_some_and(|types| types.contains(&PrimitiveType::UnixTimestamp)));
+        assert!(storable_types("bigint").is_some_and(|types| types.contains(&PrimitiveType::UnixTimestamp)));
+        assert!(storable_types("date").is_some_and(|types| !types.contains(&PrimitiveType::UnixTimestamp)));
+        assert_eq!(storable_types("jsonb"), None);
+    }
+}
@@ -1,22 +1,77 @@
+use std::path::Path;
+
 use packer::pack;
 
-use crate::mockadoc::{parser::parse_mockadoc};
+use crate::mockadoc::{evaluator::evaluate_mockadoc, parser::parse_mockadoc};
 
-pub use self::model::MockadocError;
+pub use self::model::{MockadocError, Settings};
+pub use self::evaluator::model::OutDocument;
+pub use self::writer::write_document;
 
 mod model;
 mod parser;
 mod packer;
 mod evaluator;
+mod writer;
+
+
+/// Evaluates mockadoc code that didn't come from a file. Imports are resolved relative to the working directory.
+pub fn run_mockadoc(code: &str, settings: &Settings) -> Result<Vec<OutDocument>, MockadocError> {
+    evaluate_code(code, Path::new(""), settings)
+}
+
+pub fn run_mockadoc_file(path: impl AsRef<Path>, settings: &Settings) -> Result<Vec<OutDocument>, MockadocError> {
+    let path = path.as_ref();
+    let code = std::fs::read_to_string(path)
+        .map_err(|err| MockadocError::FileReadError { path: path.to_owned(), source: err })?;
 
+    evaluate_code(&code, path.parent().unwrap_or(Path::new("")), settings)
+}
 
-pub fn run_mockadoc(code: &str) -> Result<(), MockadocError> {
+fn evaluate_code(code: &str, base_dir: &Path, settings: &Settings) -> Result<Vec<OutDocument>, MockadocError> {
     let pairs = parse_mockadoc(code)?;
-    // dbg!(&pairs);
     let packed = pack(pairs).map_err(MockadocError::PackingError)?;
-    // dbg!(&packed);
-    // let evaluation = evaluate_mockadoc(packed)?;
-    // dbg!(&evaluation);
 
-    todo!()
-}   
+    evaluate_mockadoc(packed, base_dir, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{run_mockadoc_file, write_document, Settings};
+
+    #[test]
+    fn test_documents_are_generated_and_written() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/shop.mkd");
+        let out_dir = std::env::temp_dir().join(format!("mocka_test_end_to_end_{}", std::process::id()));
+        let settings = Settings { seed: Some(42), out_dir: Some(out_dir.clone()), ..Default::default() };
+        let mut documents = run_mockadoc_file(fixture, &settings).unwrap();
+
+        let written = documents.iter_mut()
+            .map(|document| write_document(document).map(|rows| (document.title.clone(), rows)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // Orders are generated after the customers that they reference
+        assert_eq!(written, vec![ ("Customer".to_owned(), 5), ("Order".to_owned(), 20) ]);
+
+        let customers = std::fs::read_to_string(out_dir.join("Customer.csv")).unwrap();
+        let mut lines = customers.lines();
+        assert_eq!(lines.next(), Some("CustomerId"));
+        let customer_ids = lines.collect::<Vec<_>>();
+        assert_eq!(customer_ids.len(), 5);
+
+        let orders = std::fs::read_to_string(out_dir.join("Order.ndjson")).unwrap();
+        assert_eq!(orders.lines().count(), 20);
+
+        for line in orders.lines() {
+            let order: serde_json::Value = serde_json::from_str(line).unwrap();
+
+            assert!(customer_ids.contains(&order["CustomerId"].to_string().as_str()), "{line}");
+            assert!(order["Quantity"].as_i64().is_some_and(|quantity| (1..=5).contains(&quantity)), "{line}");
+            assert!(order["UnitPrice"].as_i64().is_some_and(|unit_price| (10..=20).contains(&unit_price)), "{line}");
+            assert_eq!(order["Total"].as_i64(), Some(order["Quantity"].as_i64().unwrap() * order["UnitPrice"].as_i64().unwrap()));
+        }
+    }
+}
@@ -1,7 +1,19 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
-use super::{evaluator::model::EvaluationError, parser::Rule};
+use super::{evaluator::model::EvaluationError, parser::Rule, writer::WriteError};
+
 
+/// Options for a mockadoc run. Anything left unset falls back to the file's properties block.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub row_count: Option<usize>,
+    /// Row counts for individual documents, by title, which take precedence over any count in the file
+    pub document_row_counts: Vec<(String, usize)>,
+    pub seed: Option<u64>,
+    pub out_dir: Option<PathBuf>,
+}
 
 #[derive(Debug, Error)]
 pub enum MockadocError {
@@ -13,4 +25,10 @@ pub enum MockadocError {
 
     #[error("{0}")]
     EvaluationError(#[from] EvaluationError),
+
+    #[error("{0}")]
+    WriteError(#[from] WriteError),
+
+    #[error("failed to read mockadoc file '{}': {source}", .path.display())]
+    FileReadError { path: PathBuf, source: std::io::Error },
 }
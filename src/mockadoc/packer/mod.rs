@@ -1,4 +1,5 @@
 mod packer;
+pub mod model;
 
 use pest::iterators::Pairs;
 
@@ -0,0 +1,41 @@
+use super::{MetadataProperties, MockagenIdAndMetadata};
+
+/// A column of the schema table, condensed from the rows that the packer produces
+#[derive(Debug)]
+pub struct Column {
+    pub heading: String,
+    pub data: ColumnData,
+}
+
+#[derive(Debug)]
+pub enum ColumnData {
+    Text(Vec<String>),
+    MockagenIdAndMetadata(Vec<MockagenIdAndMetadata>),
+}
+
+impl Column {
+    pub fn texts(&self) -> Option<&[String]> {
+        match &self.data {
+            ColumnData::Text(texts) => Some(texts),
+            ColumnData::MockagenIdAndMetadata(_) => None,
+        }
+    }
+
+    pub fn mockagen_ids(&self) -> Option<&[MockagenIdAndMetadata]> {
+        match &self.data {
+            ColumnData::MockagenIdAndMetadata(ids) => Some(ids),
+            ColumnData::Text(_) => None,
+        }
+    }
+}
+
+impl MockagenIdAndMetadata {
+    /// Whether the column is marked `PERSONAL`, on its own or alongside `PRIMARY TIMESTAMP`
+    pub fn is_personal(&self) -> bool {
+        matches!(self.1, Some(MetadataProperties::Personal(_) | MetadataProperties::PrimaryTimestampAndPersonal(_)))
+    }
+
+    pub fn is_primary_timestamp(&self) -> bool {
+        matches!(self.1, Some(MetadataProperties::PrimaryTimestamp(_) | MetadataProperties::PrimaryTimestampAndPersonal(_)))
+    }
+}
@@ -34,9 +34,44 @@ pub struct MockagenId(pub String);
 #[packer(rule = Rule::mockagen_identifier)]
 pub struct MockagenIdentifier(pub MockagenId);
 
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::one_to_one)]
+pub struct OneToOne;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::one_to_many)]
+pub struct OneToMany;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference_cardinality)]
+pub enum ReferenceCardinality {
+    OneToOne(OneToOne),
+    OneToMany(OneToMany),
+}
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference_percentage)]
+pub struct ReferencePercentage(pub String);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference_optional)]
+pub struct ReferenceOptional(pub ReferencePercentage);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference_document)]
+pub struct ReferenceDocument(pub String);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference_column)]
+pub struct ReferenceColumn(pub String);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::reference)]
+pub struct Reference(pub ReferenceDocument, pub Option<ReferenceColumn>, pub Option<ReferenceCardinality>, pub Option<ReferenceOptional>);
+
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::mockagen_id_and_metadata)]
-pub struct MockagenIdAndMetadata(pub MockagenIdentifier, pub Option<MetadataProperties>);
+pub struct MockagenIdAndMetadata(pub MockagenIdentifier, pub Option<MetadataProperties>, pub Option<Reference>);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::row_value)]
@@ -61,9 +96,14 @@ pub struct DocumentMembers(pub Vec<IndentedX4Text>);
 #[packer(rule = Rule::json)]
 pub struct Json;
 
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::ndjson)]
+pub struct Ndjson;
+
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::output_document_format)]
 pub enum DocumentFormat {
+    Ndjson(Ndjson),
     Json(Json),
 }
 
@@ -159,7 +199,7 @@ pub struct Title(pub Text);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::document)]
-pub struct Document(pub Title, pub Schema, pub Outputs);
+pub struct Document(pub Title, pub Option<DocumentProperties>, pub Schema, pub Outputs);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::documents)]
@@ -177,9 +217,25 @@ pub struct Path(pub PathChars);
 #[packer(rule = Rule::import_statement)]
 pub struct ImportStatement(pub Vec<Path>);
 
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::property_key)]
+pub struct PropertyKey(pub String);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::property_value)]
+pub struct PropertyValue(pub String);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::property)]
+pub struct Property(pub PropertyKey, pub PropertyValue);
+
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::properties)]
-pub struct Properties;
+pub struct Properties(pub Vec<Property>);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::document_properties)]
+pub struct DocumentProperties(pub Vec<Property>);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::EOI)]
@@ -22,3 +22,25 @@ impl DropRules for Rule {
         vec![ /* TODO drop rules */ ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mockadoc, Rule};
+
+    #[test]
+    fn test_references_name_titles_with_digits_and_hyphens() {
+        let code = "IMPORT \"orders.mkg\"\n\n\
+            # Orders-2024\n\n## Schema\n|Name|Generator|\n|---|---|\n|Order 1|`id`|\n\n\
+            ## Outputs\n- ### Tabular\n  - Formats\n    - CSV\n  - Column names\n    - Name\n  - Row values\n    - Generator\n\n\
+            # Refund\n\n## Schema\n|Name|Generator|\n|---|---|\n|Order|`order` REFERENCES Orders-2024.id ONE TO ONE|\n\n\
+            ## Outputs\n- ### Tabular\n  - Formats\n    - CSV\n  - Column names\n    - Name\n  - Row values\n    - Generator\n";
+
+        let references = parse_mockadoc(code).unwrap()
+            .flatten()
+            .filter(|pair| matches!(pair.as_rule(), Rule::reference_document | Rule::reference_column))
+            .map(|pair| pair.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(references, vec![ "Orders-2024", "id" ]);
+    }
+}
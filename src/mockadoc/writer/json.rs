@@ -0,0 +1,146 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::mockadoc::{
+    evaluator::model::{OutDocument, OutRow},
+    packer::{DocumentFormat, DocumentFormatIndented, DocumentFormats, DocumentMembers, OutputDocument},
+    writer::{find_column, model::{Result, WriteError}},
+};
+
+impl DocumentFormat {
+    pub(super) fn extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::Json(_) => "json",
+            DocumentFormat::Ndjson(_) => "ndjson",
+        }
+    }
+}
+
+/// A row serialised as an object, keeping its members in schema order
+struct JsonRow<'a>(&'a [String], &'a OutRow);
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        let JsonRow(keys, OutRow(values)) = self;
+        let mut map = serializer.serialize_map(Some(keys.len()))?;
+
+        for (key, value) in keys.iter().zip(values) {
+            map.serialize_entry(key, value)?;
+        }
+
+        map.end()
+    }
+}
+
+/// Writes rows either as a single JSON array or as newline-delimited JSON, one object at a time
+pub struct JsonWriter {
+    writer: BufWriter<File>,
+    keys: Vec<String>,
+    is_ndjson: bool,
+    row_count: usize,
+}
+
+impl JsonWriter {
+    pub fn create(path: &Path, format: &DocumentFormat, keys: &[String]) -> Result<Self> {
+        let is_ndjson = matches!(format, DocumentFormat::Ndjson(_));
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        if !is_ndjson {
+            writer.write_all(b"[")?;
+        }
+
+        Ok(Self { writer, keys: keys.to_vec(), is_ndjson, row_count: 0 })
+    }
+
+    pub fn write_row(&mut self, row: &OutRow) -> Result<()> {
+        let separator: &[u8] = match (self.is_ndjson, self.row_count) {
+            (true, _) => b"",
+            (false, 0) => b"\n",
+            (false, _) => b",\n",
+        };
+
+        self.writer.write_all(separator)?;
+        serde_json::to_writer(&mut self.writer, &JsonRow(&self.keys, row))?;
+
+        if self.is_ndjson {
+            self.writer.write_all(b"\n")?;
+        }
+
+        self.row_count += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if !self.is_ndjson {
+            self.writer.write_all(b"\n]\n")?;
+        }
+
+        self.writer.flush()
+            .map_err(WriteError::from)
+    }
+}
+
+/// Checks the output's columns and creates one file per format, ready for the document's rows
+pub fn open_json(document: &OutDocument, output: &OutputDocument) -> Result<Vec<JsonWriter>> {
+    let OutputDocument(DocumentFormats(formats), DocumentMembers(members)) = output;
+
+    let keys_column = find_column(document, members, "Members")?;
+    let keys = keys_column.texts()
+        .ok_or_else(|| WriteError::ExpectedTextColumn { document: document.title.clone(), column: keys_column.heading.clone() })?;
+
+    formats.iter()
+        .map(|DocumentFormatIndented(format)| JsonWriter::create(&document.output_path(format.extension()), format, keys))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{mockadoc::{evaluator::model::OutRow, packer::{DocumentFormat, Json, Ndjson}}, mockagen::OutValue};
+
+    use super::JsonWriter;
+
+    fn write(format: DocumentFormat, name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        let keys = [ "name".to_owned(), "age".to_owned(), "score".to_owned(), "joined".to_owned() ];
+        let rows = [
+            OutRow(vec![ OutValue::String("Tom".into()), OutValue::I64(42), OutValue::F64(0.5), OutValue::NaiveDate(NaiveDate::from_ymd_opt(2023, 5, 8).unwrap()) ]),
+            OutRow(vec![ OutValue::String("Dick".into()), OutValue::I64(7), OutValue::F64(1.25), OutValue::NaiveDate(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()) ]),
+        ];
+
+        let mut writer = JsonWriter::create(&path, &format, &keys).unwrap();
+        for row in &rows {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_json_array() {
+        let output = write(DocumentFormat::Json(Json), "mocka_test_json_array.json");
+
+        assert_eq!(output, concat!(
+            "[\n",
+            r#"{"name":"Tom","age":42,"score":0.5,"joined":"2023-05-08"},"#, "\n",
+            r#"{"name":"Dick","age":7,"score":1.25,"joined":"2024-01-31"}"#, "\n",
+            "]\n",
+        ));
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let output = write(DocumentFormat::Ndjson(Ndjson), "mocka_test_ndjson.ndjson");
+
+        assert_eq!(output, concat!(
+            r#"{"name":"Tom","age":42,"score":0.5,"joined":"2023-05-08"}"#, "\n",
+            r#"{"name":"Dick","age":7,"score":1.25,"joined":"2024-01-31"}"#, "\n",
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use itertools::Itertools;
+
+use crate::mockadoc::{
+    evaluator::model::OutDocument,
+    packer::{model::Column, DocumentFormatIndented, DocumentFormats, IndentedX4Text, OutputDocument, OutputTabular, OutputType, TabularFormatTypeIndented, TabularFormats, Text},
+    MockadocError,
+};
+
+use self::model::Result;
+
+pub mod model;
+mod tabular;
+mod json;
+
+pub use model::WriteError;
+
+/// Finds the single schema column listed under an output's section, e.g. "Column names"
+fn find_column<'a>(document: &'a OutDocument, names: &[IndentedX4Text], section: &'static str) -> Result<&'a Column> {
+    let [IndentedX4Text(Text(name))] = names else {
+        return Err(WriteError::ExpectedSingleColumn { document: document.title.clone(), section });
+    };
+
+    document.column(name)
+        .ok_or_else(|| WriteError::UnknownColumn { document: document.title.clone(), column: name.trim().to_owned() })
+}
+
+/// Each format is written to a file named after the document, so it can only be listed once across all of the outputs
+fn check_formats(document: &str, outputs: &[OutputType]) -> Result<()> {
+    let extensions = outputs.iter()
+        .flat_map(|output| match output {
+            OutputType::Tabular(OutputTabular(TabularFormats(formats), ..)) =>
+                formats.iter().map(|TabularFormatTypeIndented(format)| format.extension()).collect_vec(),
+            OutputType::Document(OutputDocument(DocumentFormats(formats), _)) =>
+                formats.iter().map(|DocumentFormatIndented(format)| format.extension()).collect_vec(),
+        });
+
+    match extensions.duplicates().next() {
+        Some(extension) => Err(WriteError::DuplicateFormat { document: document.to_owned(), format: extension.to_uppercase() }),
+        None => Ok(()),
+    }
+}
+
+/// Writes one file per format listed in each of the document's outputs. Every file is written at once, one row at a
+/// time, so the rows are only generated once and never all held in memory. Returns the number of rows written.
+pub fn write_document(document: &mut OutDocument) -> std::result::Result<usize, MockadocError> {
+    check_formats(&document.title, &document.outputs)?;
+
+    // The file name may itself contain directories
+    if let Some(dir) = document.out_dir.join(&document.file_name).parent() {
+        std::fs::create_dir_all(dir).map_err(WriteError::from)?;
+    }
+
+    let mut tabular_writers = vec![];
+    let mut json_writers = vec![];
+
+    for output in &document.outputs {
+        match output {
+            OutputType::Tabular(tabular) => tabular_writers.extend(tabular::open_tabular(document, tabular)?),
+            OutputType::Document(document_output) => json_writers.extend(json::open_json(document, document_output)?),
+        }
+    }
+
+    let mut row_count = 0;
+
+    for row in &mut document.rows {
+        // Rows are generated while they're written, so generation can fail part way through a file
+        let row = row?;
+
+        for writer in &mut tabular_writers {
+            writer.write_row(&row)?;
+        }
+
+        for writer in &mut json_writers {
+            writer.write_row(&row)?;
+        }
+
+        row_count += 1;
+    }
+
+    for writer in tabular_writers {
+        writer.finish()?;
+    }
+
+    for writer in json_writers {
+        writer.finish()?;
+    }
+
+    Ok(row_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockadoc::packer::{
+        Csv, DocumentFormat, DocumentFormatIndented, DocumentFormats, DocumentMembers, Json, Ndjson, OutputDocument, OutputTabular, OutputTabularFormatType, OutputType, TabularColumnNames, TabularFormatTypeIndented, TabularFormats, TabularRowValues, Tsv
+    };
+
+    use super::{check_formats, WriteError};
+
+    fn tabular(formats: Vec<OutputTabularFormatType>) -> OutputType {
+        let formats = formats.into_iter().map(TabularFormatTypeIndented).collect();
+
+        OutputType::Tabular(OutputTabular(TabularFormats(formats), TabularColumnNames(vec![]), TabularRowValues(vec![])))
+    }
+
+    fn document(formats: Vec<DocumentFormat>) -> OutputType {
+        let formats = formats.into_iter().map(DocumentFormatIndented).collect();
+
+        OutputType::Document(OutputDocument(DocumentFormats(formats), DocumentMembers(vec![])))
+    }
+
+    #[test]
+    fn test_formats_are_only_written_once() {
+        let outputs = [ tabular(vec![ OutputTabularFormatType::Csv(Csv), OutputTabularFormatType::Tsv(Tsv) ]), document(vec![ DocumentFormat::Json(Json), DocumentFormat::Ndjson(Ndjson) ]) ];
+        assert!(check_formats("Transaction", &outputs).is_ok());
+
+        let repeated = [ tabular(vec![ OutputTabularFormatType::Csv(Csv), OutputTabularFormatType::Csv(Csv) ]) ];
+        assert!(matches!(check_formats("Transaction", &repeated), Err(WriteError::DuplicateFormat { format, .. }) if format == "CSV"));
+
+        let across_outputs = [ document(vec![ DocumentFormat::Json(Json) ]), document(vec![ DocumentFormat::Json(Json) ]) ];
+        assert!(matches!(check_formats("Transaction", &across_outputs), Err(WriteError::DuplicateFormat { format, .. }) if format == "JSON"));
+    }
+}
@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WriteError {
+    #[error("failed to write output file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("failed to write tabular output: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("failed to write document output: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("document '{document}' has no column named '{column}'")]
+    UnknownColumn { document: String, column: String },
+
+    #[error("'{section}' in document '{document}' should name exactly one column")]
+    ExpectedSingleColumn { document: String, section: &'static str },
+
+    #[error("column '{column}' in document '{document}' should contain text, not generators")]
+    ExpectedTextColumn { document: String, column: String },
+
+    #[error("column '{column}' in document '{document}' should contain generators")]
+    ExpectedGeneratorColumn { document: String, column: String },
+
+    #[error("'{id}' is listed under 'Row values' in document '{document}' but isn't generated for its rows")]
+    UngeneratedValue { document: String, id: String },
+
+    #[error("document '{document}' lists the {format} format more than once")]
+    DuplicateFormat { document: String, format: String },
+}
+
+pub type Result<T> = std::result::Result<T, WriteError>;
@@ -0,0 +1,116 @@
+use std::{fs::File, path::Path};
+
+use csv::{Writer, WriterBuilder};
+
+use crate::mockadoc::{
+    evaluator::model::{OutDocument, OutRow},
+    packer::{MockagenId, MockagenIdAndMetadata, MockagenIdentifier, OutputTabular, OutputTabularFormatType, TabularColumnNames, TabularFormatTypeIndented, TabularFormats, TabularRowValues},
+    writer::{find_column, model::{Result, WriteError}},
+};
+
+impl OutputTabularFormatType {
+    fn delimiter(&self) -> u8 {
+        match self {
+            OutputTabularFormatType::Csv(_) => b',',
+            OutputTabularFormatType::Tsv(_) => b'\t',
+        }
+    }
+
+    pub(super) fn extension(&self) -> &'static str {
+        match self {
+            OutputTabularFormatType::Csv(_) => "csv",
+            OutputTabularFormatType::Tsv(_) => "tsv",
+        }
+    }
+}
+
+/// Writes delimited rows, quoting any value that contains the delimiter, a quote or a line break
+pub struct TabularWriter {
+    writer: Writer<File>,
+    /// Where each of the written values is found in a generated row, in the order that they're written
+    positions: Vec<usize>,
+}
+
+impl TabularWriter {
+    pub fn create(path: &Path, format: &OutputTabularFormatType, header: &[String], positions: Vec<usize>) -> Result<Self> {
+        let mut writer = WriterBuilder::new()
+            .delimiter(format.delimiter())
+            .from_path(path)?;
+
+        writer.write_record(header)?;
+
+        Ok(Self { writer, positions })
+    }
+
+    pub fn write_row(&mut self, OutRow(values): &OutRow) -> Result<()> {
+        self.writer.write_record(self.positions.iter().map(|position| values[*position].to_string()))
+            .map_err(WriteError::from)
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()
+            .map_err(WriteError::from)
+    }
+}
+
+/// Checks the output's columns and creates one file per format, ready for the document's rows
+pub fn open_tabular(document: &OutDocument, output: &OutputTabular) -> Result<Vec<TabularWriter>> {
+    let OutputTabular(TabularFormats(formats), TabularColumnNames(column_names), TabularRowValues(row_values)) = output;
+
+    let header_column = find_column(document, column_names, "Column names")?;
+    let header = header_column.texts()
+        .ok_or_else(|| WriteError::ExpectedTextColumn { document: document.title.clone(), column: header_column.heading.clone() })?;
+
+    let values_column = find_column(document, row_values, "Row values")?;
+    let values = values_column.mockagen_ids()
+        .ok_or_else(|| WriteError::ExpectedGeneratorColumn { document: document.title.clone(), column: values_column.heading.clone() })?;
+
+    let positions = values.iter()
+        .map(|MockagenIdAndMetadata(MockagenIdentifier(MockagenId(id)), ..)| document.rows.position_of(id)
+            .ok_or_else(|| WriteError::UngeneratedValue { document: document.title.clone(), id: id.to_owned() }))
+        .collect::<Result<Vec<_>>>()?;
+
+    formats.iter()
+        .map(|TabularFormatTypeIndented(format)| TabularWriter::create(&document.output_path(format.extension()), format, header, positions.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mockadoc::{evaluator::model::OutRow, packer::{Csv, OutputTabularFormatType, Tsv}}, mockagen::OutValue};
+
+    use super::TabularWriter;
+
+    fn write(format: OutputTabularFormatType, name: &str, positions: Vec<usize>) -> String {
+        let path = std::env::temp_dir().join(name);
+        let header = [ "Name".to_owned(), "Quote".to_owned() ];
+        let row = OutRow(vec![ OutValue::String("Smith, Tom".into()), OutValue::I64(7), OutValue::String("say \"hi\"\tthere".into()) ]);
+
+        let mut writer = TabularWriter::create(&path, &format, &header, positions).unwrap();
+        writer.write_row(&row).unwrap();
+        writer.finish().unwrap();
+
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let output = write(OutputTabularFormatType::Csv(Csv), "mocka_test_csv_quoting.csv", vec![ 0, 2 ]);
+
+        assert_eq!(output, "Name,Quote\n\"Smith, Tom\",\"say \"\"hi\"\"\tthere\"\n");
+    }
+
+    #[test]
+    fn test_tsv_quoting() {
+        let output = write(OutputTabularFormatType::Tsv(Tsv), "mocka_test_tsv_quoting.tsv", vec![ 0, 2 ]);
+
+        assert_eq!(output, "Name\tQuote\nSmith, Tom\t\"say \"\"hi\"\"\tthere\"\n");
+    }
+
+    #[test]
+    fn test_row_values_are_picked_in_order() {
+        let output = write(OutputTabularFormatType::Csv(Csv), "mocka_test_row_values.csv", vec![ 1, 0 ]);
+
+        assert_eq!(output, "Name,Quote\n7,\"Smith, Tom\"\n");
+    }
+}
@@ -0,0 +1,504 @@
+use std::cmp::Ordering;
+
+use chrono::Duration;
+
+use crate::mockagen::{
+    evaluator::{
+        generators::{Generator2, GeneratorEnum},
+        model::{Context, EvaluationError, OutValue, Result},
+        types::{PrimitiveType, TypeChecker, ValueType},
+    },
+    packer::packer::{
+        ArithmeticExpression, ArithmeticFunction, ArithmeticGroup, ArithmeticOperand, ArithmeticOperation, ArithmeticOperator, ArithmeticStep, ArithmeticTerm, ArithmeticValue, DurationUnit, FunctionName, HigherOrderValue, IntegerLiteral, NumberLiteral, RealLiteral
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl From<ArithmeticOperator> for Operator {
+    fn from(value: ArithmeticOperator) -> Self {
+        match value {
+            ArithmeticOperator::Add(_) => Self::Add,
+            ArithmeticOperator::Subtract(_) => Self::Subtract,
+            ArithmeticOperator::Multiply(_) => Self::Multiply,
+            ArithmeticOperator::Divide(_) => Self::Divide,
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Min,
+    Max,
+    Round,
+    Floor,
+    Ceil,
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Round => "round",
+            Self::Floor => "floor",
+            Self::Ceil => "ceil",
+        })
+    }
+}
+
+/// A value partway through a calculation. Durations such as `3 days` only exist to be added to dates.
+#[derive(Debug)]
+enum Quantity {
+    Value(OutValue),
+    Duration(Duration),
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quantity::Value(value) => f.write_fmt(format_args!("'{value}'")),
+            Quantity::Duration(duration) => f.write_fmt(format_args!("{} seconds", duration.num_seconds())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Expression {
+    Number(OutValue),
+    Value(Box<GeneratorEnum>),
+    /// An amount of the given unit, e.g. `integer 1 30 days`
+    Duration(Box<Expression>, Duration),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Function(Function, Vec<Expression>),
+}
+
+impl Expression {
+    /// Multiplication and division are applied first, then addition and subtraction from left to right
+    fn from_steps(first: ArithmeticOperand, steps: Vec<ArithmeticStep>) -> Result<Self> {
+        let mut terms = vec![];
+        let mut pending = Operator::Add;
+        let mut product = Self::try_from(first)?;
+
+        for ArithmeticStep(operator, operand) in steps {
+            let operand = Self::try_from(operand)?;
+
+            match Operator::from(operator) {
+                operator @ (Operator::Multiply | Operator::Divide) =>
+                    product = Self::Binary(operator, Box::new(product), Box::new(operand)),
+
+                operator => {
+                    terms.push((pending, product));
+                    pending = operator;
+                    product = operand;
+                },
+            }
+        }
+
+        terms.push((pending, product));
+
+        let mut terms = terms.into_iter();
+        let (_, first) = terms.next().expect("there is always at least one term");
+
+        Ok(terms.fold(first, |sum, (operator, term)| Self::Binary(operator, Box::new(sum), Box::new(term))))
+    }
+
+    fn evaluate(&self, ctxt: &mut Context) -> Result<Quantity> {
+        match self {
+            Self::Number(number) => Ok(Quantity::Value(number.clone())),
+            Self::Value(gen) => Ok(Quantity::Value(gen.generate_value(ctxt)?)),
+
+            Self::Duration(amount, unit) => {
+                let duration = match amount.evaluate(ctxt)? {
+                    Quantity::Value(OutValue::I64(amount)) => i32::try_from(amount).ok()
+                        .and_then(|amount| unit.checked_mul(amount))
+                        .ok_or(amount.to_string()),
+                    Quantity::Value(OutValue::F64(amount)) => Some((unit.num_milliseconds() as f64 * amount).round())
+                        .filter(|millis| millis.abs() < i64::MAX as f64)
+                        .and_then(|millis| Duration::try_milliseconds(millis as i64))
+                        .ok_or(amount.to_string()),
+                    _ => return Err(EvaluationError::InvalidArithmetic("a duration must be a number of units".into())),
+                };
+
+                duration.map(Quantity::Duration)
+                    .map_err(|amount| EvaluationError::InvalidArithmetic(format!("a duration of {amount} units is out of range")))
+            },
+
+            Self::Binary(operator, left, right) => apply(*operator, left.evaluate(ctxt)?, right.evaluate(ctxt)?),
+
+            Self::Function(function, arguments) => {
+                let arguments = arguments.iter()
+                    .map(|argument| match argument.evaluate(ctxt)? {
+                        Quantity::Value(value) => Ok(value),
+                        duration => Err(EvaluationError::InvalidArithmetic(format!("{function} can't be given {duration}"))),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                call(*function, arguments).map(Quantity::Value)
+            },
+        }
+    }
+
+    pub fn dependencies(&self) -> Vec<&str> {
+        match self {
+            Self::Number(_) => vec![],
+            Self::Value(gen) => gen.dependencies(),
+            Self::Duration(amount, _) => amount.dependencies(),
+            Self::Binary(_, left, right) => left.dependencies().into_iter().chain(right.dependencies()).collect(),
+            Self::Function(_, arguments) => arguments.iter().flat_map(Self::dependencies).collect(),
+        }
+    }
+
+    /// Durations have no type of their own, so adding one to a date leaves the date's type
+    pub fn value_type(&self, checker: &mut TypeChecker) -> ValueType {
+        match self {
+            Self::Number(OutValue::I64(_)) => PrimitiveType::Integer.into(),
+            Self::Number(_) => PrimitiveType::Real.into(),
+            Self::Value(gen) => gen.value_type(checker),
+            Self::Duration(_, _) => ValueType::default(),
+
+            Self::Binary(operator, left, right) => {
+                let (left, right) = (left.value_type(checker), right.value_type(checker));
+
+                match (left.is_empty(), right.is_empty()) {
+                    (true, _) => right,
+                    (_, true) => left,
+                    _ => left.primitives()
+                        .flat_map(|l| right.primitives().filter_map(move |r| result_type(*operator, l, r)))
+                        .map(ValueType::from)
+                        .collect(),
+                }
+            },
+
+            Self::Function(Function::Min | Function::Max, arguments) =>
+                arguments.iter()
+                    .map(|argument| argument.value_type(checker))
+                    .collect(),
+
+            Self::Function(_, arguments) if arguments.len() == 1 => PrimitiveType::Integer.into(),
+            Self::Function(_, _) => PrimitiveType::Real.into(),
+        }
+    }
+}
+
+fn result_type(operator: Operator, left: PrimitiveType, right: PrimitiveType) -> Option<PrimitiveType> {
+    use PrimitiveType::*;
+
+    match (operator, left, right) {
+        (Operator::Divide, Integer | Real, Integer | Real) => Some(Real),
+        (_, Integer, Integer) => Some(Integer),
+        (_, Integer | Real, Integer | Real) => Some(Real),
+        (Operator::Subtract, Date, Date) => Some(Integer),
+        (Operator::Subtract, DateTime | UnixTimestamp, DateTime | UnixTimestamp) => Some(Integer),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &OutValue) -> Option<f64> {
+    match value {
+        OutValue::I64(value) => Some(*value as f64),
+        OutValue::F64(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Integers stay integers, except when divided
+fn apply(operator: Operator, left: Quantity, right: Quantity) -> Result<Quantity> {
+    use Quantity::{Duration as Span, Value};
+
+    let result = match (operator, &left, &right) {
+        (_, Value(OutValue::I64(a)), Value(OutValue::I64(b))) if operator != Operator::Divide => match operator {
+            Operator::Add => a.checked_add(*b),
+            Operator::Subtract => a.checked_sub(*b),
+            _ => a.checked_mul(*b),
+        }.map(|value| Value(OutValue::I64(value))),
+
+        (_, Value(a), Value(b)) if as_f64(a).is_some() && as_f64(b).is_some() => {
+            let (a, b) = (as_f64(a).unwrap_or_default(), as_f64(b).unwrap_or_default());
+
+            match operator {
+                Operator::Add => Some(a + b),
+                Operator::Subtract => Some(a - b),
+                Operator::Multiply => Some(a * b),
+                Operator::Divide => (b != 0.0).then(|| a / b),
+            }.map(|value| Value(OutValue::F64(value)))
+        },
+
+        (Operator::Add, Value(OutValue::NaiveDate(date)), Span(duration))
+        | (Operator::Add, Span(duration), Value(OutValue::NaiveDate(date))) =>
+            date.checked_add_signed(*duration).map(|date| Value(OutValue::NaiveDate(date))),
+
+        (Operator::Subtract, Value(OutValue::NaiveDate(date)), Span(duration)) =>
+            date.checked_sub_signed(*duration).map(|date| Value(OutValue::NaiveDate(date))),
+
+        (Operator::Add, Value(OutValue::DateTime(instant, format)), Span(duration))
+        | (Operator::Add, Span(duration), Value(OutValue::DateTime(instant, format))) =>
+            instant.checked_add_signed(*duration).map(|instant| Value(OutValue::DateTime(instant, *format))),
+
+        (Operator::Subtract, Value(OutValue::DateTime(instant, format)), Span(duration)) =>
+            instant.checked_sub_signed(*duration).map(|instant| Value(OutValue::DateTime(instant, *format))),
+
+        (Operator::Subtract, Value(OutValue::NaiveDate(a)), Value(OutValue::NaiveDate(b))) =>
+            Some(Value(OutValue::I64(a.signed_duration_since(*b).num_days()))),
+
+        (Operator::Subtract, Value(OutValue::DateTime(a, _)), Value(OutValue::DateTime(b, _))) =>
+            Some(Value(OutValue::I64(a.signed_duration_since(*b).num_seconds()))),
+
+        (Operator::Add, Span(a), Span(b)) => a.checked_add(b).map(Span),
+        (Operator::Subtract, Span(a), Span(b)) => a.checked_sub(b).map(Span),
+
+        _ => None,
+    };
+
+    result.ok_or_else(|| EvaluationError::InvalidArithmetic(format!("{left} {operator} {right}")))
+}
+
+fn compare(a: &OutValue, b: &OutValue) -> Option<Ordering> {
+    match (a, b) {
+        (OutValue::I64(a), OutValue::I64(b)) => Some(a.cmp(b)),
+        (OutValue::NaiveDate(a), OutValue::NaiveDate(b)) => Some(a.cmp(b)),
+        (OutValue::DateTime(a, _), OutValue::DateTime(b, _)) => Some(a.cmp(b)),
+        (a, b) => as_f64(a)?.partial_cmp(&as_f64(b)?),
+    }
+}
+
+fn call(function: Function, arguments: Vec<OutValue>) -> Result<OutValue> {
+    let invalid = |arguments: &[OutValue]| {
+        let arguments = arguments.iter().map(|argument| format!("'{argument}'")).collect::<Vec<_>>();
+
+        EvaluationError::InvalidArithmetic(format!("{function}({})", arguments.join(", ")))
+    };
+
+    match function {
+        Function::Min | Function::Max => {
+            let wanted = match function {
+                Function::Min => Ordering::Less,
+                _ => Ordering::Greater,
+            };
+
+            let mut chosen = arguments[0].clone();
+            for argument in &arguments[1..] {
+                match compare(argument, &chosen) {
+                    Some(ordering) if ordering == wanted => chosen = argument.clone(),
+                    Some(_) => {},
+                    None => return Err(invalid(&arguments)),
+                }
+            }
+
+            Ok(chosen)
+        },
+
+        Function::Round | Function::Floor | Function::Ceil => {
+            let round = |value: f64| match function {
+                Function::Round => value.round(),
+                Function::Floor => value.floor(),
+                _ => value.ceil(),
+            };
+
+            match arguments.as_slice() {
+                [ value ] => as_f64(value)
+                    .map(|value| OutValue::I64(round(value) as i64))
+                    .ok_or_else(|| invalid(&arguments)),
+
+                [ value, OutValue::I64(digits) ] => as_f64(value)
+                    .map(|value| {
+                        let scale = 10f64.powi(*digits as i32);
+                        OutValue::F64(round(value * scale) / scale)
+                    })
+                    .ok_or_else(|| invalid(&arguments)),
+
+                _ => Err(invalid(&arguments)),
+            }
+        },
+    }
+}
+
+impl TryFrom<ArithmeticOperand> for Expression {
+    type Error = EvaluationError;
+
+    fn try_from(ArithmeticOperand(term, unit): ArithmeticOperand) -> Result<Self> {
+        let term = Self::try_from(term)?;
+
+        let Some(unit) = unit else {
+            return Ok(term);
+        };
+
+        let unit = match unit {
+            DurationUnit::Weeks(_) => Duration::weeks(1),
+            DurationUnit::Days(_) => Duration::days(1),
+            DurationUnit::Hours(_) => Duration::hours(1),
+            DurationUnit::Minutes(_) => Duration::minutes(1),
+            DurationUnit::Seconds(_) => Duration::seconds(1),
+        };
+
+        Ok(Self::Duration(Box::new(term), unit))
+    }
+}
+
+impl TryFrom<ArithmeticTerm> for Expression {
+    type Error = EvaluationError;
+
+    fn try_from(value: ArithmeticTerm) -> Result<Self> {
+        match value {
+            ArithmeticTerm::Group(ArithmeticGroup(expression)) => {
+                let ArithmeticExpression(first, steps) = *expression;
+                Self::from_steps(first, steps)
+            },
+            ArithmeticTerm::Function(function) => function.try_into(),
+            ArithmeticTerm::Number(NumberLiteral::Integer(IntegerLiteral(number))) => Ok(Self::Number(OutValue::I64(number))),
+            ArithmeticTerm::Number(NumberLiteral::Real(RealLiteral(number))) => Ok(Self::Number(OutValue::F64(number))),
+            ArithmeticTerm::Primitive(value) => Ok(Self::Value(Box::new(value.try_into()?))),
+            ArithmeticTerm::Identifier(id) => Ok(Self::Value(Box::new(HigherOrderValue::IdentifierValue(id).try_into()?))),
+        }
+    }
+}
+
+impl TryFrom<ArithmeticFunction> for Expression {
+    type Error = EvaluationError;
+
+    fn try_from(ArithmeticFunction(name, arguments): ArithmeticFunction) -> Result<Self> {
+        let function = match name {
+            FunctionName::Min(_) => Function::Min,
+            FunctionName::Max(_) => Function::Max,
+            FunctionName::Round(_) => Function::Round,
+            FunctionName::Floor(_) => Function::Floor,
+            FunctionName::Ceil(_) => Function::Ceil,
+        };
+
+        // Rounding takes the value along with an optional number of decimal places
+        if matches!(function, Function::Round | Function::Floor | Function::Ceil) && arguments.len() > 2 {
+            return Err(EvaluationError::WrongArgumentCount { function: function.to_string(), found: arguments.len() });
+        }
+
+        let arguments = arguments.into_iter()
+            .map(|ArithmeticExpression(first, steps)| Self::from_steps(first, steps))
+            .collect::<Result<_>>()?;
+
+        Ok(Self::Function(function, arguments))
+    }
+}
+
+/// Derives a number or date from other values, e.g. `price * quantity` or `signup + integer 1 30 days`
+#[derive(Debug)]
+pub struct ArithmeticGen(pub Expression);
+
+impl ArithmeticGen {
+    pub fn new(value: ArithmeticValue) -> Result<Self> {
+        let expression = match value {
+            ArithmeticValue::Operation(ArithmeticOperation(first, steps)) => Expression::from_steps(first, steps)?,
+            ArithmeticValue::Function(function) => function.try_into()?,
+        };
+
+        Ok(Self(expression))
+    }
+}
+
+impl Generator2 for ArithmeticGen {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        match self.0.evaluate(ctxt)? {
+            Quantity::Value(value) => Ok(value),
+            duration => Err(EvaluationError::InvalidArithmetic(format!("{duration} is a duration rather than a value"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::mockagen::{
+        evaluator::{
+            generators::{Generator2, GeneratorEnum},
+            model::{Bindings, Context, EvaluationError, OutValue},
+        },
+        packer::packer::{
+            ArithmeticOperand, ArithmeticOperation, ArithmeticOperator, ArithmeticStep, ArithmeticTerm, ArithmeticValue, HigherOrderValue, Identifier, IdentifierValue, IntegerLiteral, IntegerUpperBound, IntegerValue, NumberLiteral, OperatorAdd, OperatorDivide, OperatorMultiply, OperatorSubtract, PrimitiveValue
+        },
+    };
+
+    use super::{call, ArithmeticGen, Expression, Function, Operator};
+
+    fn number(n: i64) -> ArithmeticOperand {
+        ArithmeticOperand(ArithmeticTerm::Number(NumberLiteral::Integer(IntegerLiteral(n))), None)
+    }
+
+    #[test]
+    fn test_precedence() {
+        // 1 + 2 * 3 - 4 / 2
+        let steps = vec![
+            ArithmeticStep(ArithmeticOperator::Add(OperatorAdd), number(2)),
+            ArithmeticStep(ArithmeticOperator::Multiply(OperatorMultiply), number(3)),
+            ArithmeticStep(ArithmeticOperator::Subtract(OperatorSubtract), number(4)),
+            ArithmeticStep(ArithmeticOperator::Divide(OperatorDivide), number(2)),
+        ];
+
+        let gen = ArithmeticGen::new(ArithmeticValue::Operation(ArithmeticOperation(number(1), steps))).unwrap();
+        let mut context = Context::with_seed(Bindings::default(), 42);
+
+        assert!(matches!(gen.generate_value(&mut context).unwrap(), OutValue::F64(value) if value == 5.0));
+    }
+
+    #[test]
+    fn test_derived_values_match_their_inputs() {
+        let mut bindings = Bindings::default();
+        let price = PrimitiveValue::Integer(IntegerValue(IntegerLiteral(1), Some(IntegerUpperBound::Literal(IntegerLiteral(1_000_000)))));
+        bindings.add("price".into(), price.try_into().unwrap(), None).unwrap();
+
+        let price = GeneratorEnum::try_from(HigherOrderValue::IdentifierValue(IdentifierValue(Identifier("price".into())))).unwrap();
+        let doubled = Expression::Binary(Operator::Multiply, Box::new(Expression::Value(Box::new(price))), Box::new(Expression::Number(OutValue::I64(2))));
+        bindings.add("doubled".into(), GeneratorEnum::Arithmetic(ArithmeticGen(doubled)), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        for row in context.generate_rows(&["price", "doubled"], 10) {
+            let row = row.unwrap();
+            assert!(matches!((&*row[0], &*row[1]), (OutValue::I64(price), OutValue::I64(doubled)) if price * 2 == *doubled));
+        }
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        let day = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+        let signup = || Box::new(Expression::Number(OutValue::NaiveDate(day)));
+        let days = |n| Box::new(Expression::Duration(Box::new(Expression::Number(OutValue::I64(n))), chrono::Duration::days(1)));
+        let mut context = Context::with_seed(Bindings::default(), 42);
+
+        let renewal = ArithmeticGen(Expression::Binary(Operator::Add, signup(), days(30)));
+        assert_eq!(renewal.generate_value(&mut context).unwrap().to_string(), "2023-02-14");
+
+        let elapsed = ArithmeticGen(Expression::Binary(Operator::Subtract, Box::new(renewal.0), signup()));
+        assert!(matches!(elapsed.generate_value(&mut context).unwrap(), OutValue::I64(30)));
+
+        let invalid = ArithmeticGen(Expression::Binary(Operator::Multiply, signup(), days(2)));
+        assert!(invalid.generate_value(&mut context).is_err());
+
+        let overflowing = ArithmeticGen(Expression::Binary(Operator::Add, signup(), days(i64::from(i32::MAX) + 1)));
+        assert!(matches!(overflowing.generate_value(&mut context), Err(EvaluationError::InvalidArithmetic(_))));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert!(matches!(call(Function::Round, vec![ OutValue::F64(2.5) ]).unwrap(), OutValue::I64(3)));
+        assert!(matches!(call(Function::Floor, vec![ OutValue::F64(-1.5) ]).unwrap(), OutValue::I64(-2)));
+        assert!(matches!(call(Function::Round, vec![ OutValue::F64(1.2345), OutValue::I64(2) ]).unwrap(), OutValue::F64(value) if value == 1.23));
+        assert!(matches!(call(Function::Min, vec![ OutValue::I64(4), OutValue::F64(2.5) ]).unwrap(), OutValue::F64(value) if value == 2.5));
+        assert!(matches!(call(Function::Max, vec![ OutValue::I64(4), OutValue::F64(2.5) ]).unwrap(), OutValue::I64(4)));
+        assert!(call(Function::Max, vec![ OutValue::I64(4), OutValue::String("a".into()) ]).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::mockagen::evaluator::model::{Bindings, EvaluationError, Result};
+
+type Graph<'a> = HashMap<&'a str, Vec<&'a str>>;
+
+/// Checks that every identifier that a definition refers to is bound, and that no identifier depends on itself.
+/// Values are generated lazily, so otherwise these would only be found when (and if) the offending value was generated.
+pub fn check_dependencies(bindings: &Bindings) -> Result<()> {
+    let graph: Graph = bindings.iter()
+        .map(|(id, gen)| (id, gen.dependencies().into_iter().unique().collect()))
+        .collect();
+
+    let unbound = graph.iter()
+        .sorted()
+        .flat_map(|(id, deps)| deps.iter()
+            .filter(|dep| !graph.contains_key(*dep))
+            .map(|dep| EvaluationError::UnboundIdentifier(dep.to_string()).in_definition(id.to_string(), bindings.span_of(id))))
+        .collect_vec();
+
+    EvaluationError::all(unbound)?;
+
+    let mut explored = HashSet::new();
+
+    for id in graph.keys().sorted() {
+        if let Some(cycle) = find_cycle(id, &graph, &mut explored, &mut vec![]) {
+            let id = cycle[0].to_owned();
+            let cycle = cycle.into_iter().map(str::to_owned).collect();
+
+            return Err(EvaluationError::DependencyCycle(cycle).in_definition(id.clone(), bindings.span_of(&id)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Depth-first search that returns the first cycle found, starting and ending with the same identifier
+fn find_cycle<'a>(id: &'a str, graph: &Graph<'a>, explored: &mut HashSet<&'a str>, path: &mut Vec<&'a str>) -> Option<Vec<&'a str>> {
+    if let Some(start) = path.iter().position(|step| *step == id) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(id);
+
+        return Some(cycle);
+    }
+
+    // Anything explored before that isn't on the current path can't lead back to it
+    if !explored.insert(id) {
+        return None;
+    }
+
+    path.push(id);
+    let cycle = graph.get(id)
+        .into_iter()
+        .flatten()
+        .find_map(|dep| find_cycle(dep, graph, explored, path));
+    path.pop();
+
+    cycle
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockagen::{
+        evaluator::{generators::GeneratorEnum, model::{Bindings, EvaluationError}},
+        packer::packer::{HigherOrderValue, Identifier, IdentifierValue, JoinValue, Value},
+    };
+
+    use super::check_dependencies;
+
+    fn reference(id: &str) -> Value {
+        Value::HigherOrder(HigherOrderValue::IdentifierValue(IdentifierValue(Identifier(id.to_owned()))))
+    }
+
+    fn bind(definitions: &[(&str, Value)]) -> Bindings {
+        let mut bindings = Bindings::default();
+
+        for (id, value) in definitions {
+            bindings.add(id.to_string(), GeneratorEnum::try_from(value.clone()).unwrap(), None).unwrap();
+        }
+
+        bindings
+    }
+
+    #[test]
+    fn test_cycles_are_reported_with_their_path() {
+        let bindings = bind(&[
+            ("a", reference("b")),
+            ("b", Value::HigherOrder(HigherOrderValue::JoinValue(JoinValue(None, vec![ reference("c"), reference("a") ])))),
+            ("c", reference("d")),
+            ("d", Value::HigherOrder(HigherOrderValue::JoinValue(JoinValue(None, vec![])))),
+        ]);
+
+        let err = check_dependencies(&bindings).unwrap_err();
+        assert_eq!(err.to_string(), "invalid definition of 'a': dependency cycle: a -> b -> a");
+
+        assert!(check_dependencies(&bind(&[ ("a", reference("b")), ("b", reference("c")), ("c", reference("d")), ("d", reference("b")) ])).is_err());
+        assert!(check_dependencies(&bind(&[ ("a", reference("b")), ("b", reference("c")), ("c", reference("b")) ])).is_err());
+    }
+
+    #[test]
+    fn test_every_unbound_reference_is_reported() {
+        let bindings = bind(&[
+            ("a", reference("missing")),
+            ("b", Value::HigherOrder(HigherOrderValue::JoinValue(JoinValue(None, vec![ reference("a"), reference("also-missing") ])))),
+        ]);
+
+        match check_dependencies(&bindings) {
+            Err(EvaluationError::Several(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("expected two unbound references, found {other:?}"),
+        }
+    }
+}
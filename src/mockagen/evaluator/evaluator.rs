@@ -2,52 +2,69 @@ use std::rc::Rc;
 
 use itertools::Itertools;
 
-use crate::mockagen::{evaluator::{generators::{GeneratorEnum, NestedGenerator, ValueTree}, model::{Bindings, Result}}, packer::packer::{AssignIds, Definition, Identifier, MultiValDef, Names, NestedDefinition, SingleDefinition, SingleValDef}};
+use crate::mockagen::{evaluator::{generators::{GeneratorEnum, NestedGenerator, UniqueGen, ValueTree, DEFAULT_UNIQUE_RETRIES}, model::{Bindings, DefinitionSpans, Result}}, packer::packer::{AssignIds, Definition, Identifier, LengthLiteral, MultiValDef, Names, NestedDefinition, SingleDefinition, SingleValDef, Unique, UniqueRetries}};
 
 pub trait Evaluate {
-    fn evaluate(self, bindings: Bindings) -> Result<Bindings>;
+    fn evaluate(self, bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings>;
+}
+
+/// Stops a generator from repeating its values if its definition is `UNIQUE`
+fn with_uniqueness(id: &str, gen: GeneratorEnum, unique: &Option<Unique>) -> GeneratorEnum {
+    match unique {
+        None => gen,
+        Some(Unique(retries)) => {
+            let retries = retries.as_ref()
+                .map(|UniqueRetries(LengthLiteral(retries))| *retries)
+                .unwrap_or(DEFAULT_UNIQUE_RETRIES);
+
+            GeneratorEnum::Unique(UniqueGen::new(id.to_owned(), retries, gen))
+        },
+    }
 }
 
 impl Evaluate for SingleValDef {
-    fn evaluate(self, mut bindings: Bindings) -> Result<Bindings> {
-        let Self(Identifier(id), value) = self;
-        bindings.add(id, value.into())?;
+    fn evaluate(self, mut bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings> {
+        let Self(unique, Identifier(id), value) = self;
+        let gen = with_uniqueness(&id, value.try_into()?, &unique);
+        bindings.add(id, gen, spans.definition.clone())?;
         Ok(bindings)
     }
 }
 
 impl Evaluate for MultiValDef {
-    fn evaluate(self, mut bindings: Bindings) -> Result<Bindings> {
-        let Self(Identifier(id), _, values) = self;
-        bindings.add(id, values.into())?;
+    fn evaluate(self, mut bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings> {
+        let Self(unique, Identifier(id), _, values) = self;
+        let gen = with_uniqueness(&id, values.try_into()?, &unique);
+        bindings.add(id, gen, spans.definition.clone())?;
         Ok(bindings)
     }
 }
 
 impl Evaluate for SingleDefinition {
-    fn evaluate(self, bindings: Bindings) -> Result<Bindings> {
+    fn evaluate(self, bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings> {
         match self {
-            SingleDefinition::SingleVal(single) => single.evaluate(bindings),
-            SingleDefinition::MultiVal(multi) => multi.evaluate(bindings),
+            SingleDefinition::SingleVal(single) => single.evaluate(bindings, spans),
+            SingleDefinition::MultiVal(multi) => multi.evaluate(bindings, spans),
         }
     }
 }
 
 impl Evaluate for NestedDefinition {
-    fn evaluate(self, mut bindings: Bindings) -> Result<Bindings> {
-        let Self(maybe_using_ids, AssignIds(Names(assign_ids)), nested_clauses) = self;
+    fn evaluate(self, mut bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings> {
+        let Self(maybe_using_ids, unique, AssignIds(Names(assign_ids)), nested_clauses) = self;
         let ids = maybe_using_ids.iter()
             .flat_map(|u| u.0.0.iter())
             .chain(assign_ids.iter())
             .map(|i| i.0.as_str())
             .collect_vec();
 
-        let value_tree = Rc::new(ValueTree::from_nested_clauses(ids.as_slice(), nested_clauses)?);
-        let using_id_offset = maybe_using_ids.map(|u| u.0.0.len()).unwrap_or(0);
+        let using_id_offset = maybe_using_ids.as_ref().map(|u| u.0.0.len()).unwrap_or(0);
+        let value_tree = Rc::new(ValueTree::from_nested_clauses(ids.as_slice(), using_id_offset, nested_clauses, spans)?);
         let depths = using_id_offset..assign_ids.len() + using_id_offset;
 
         for (Identifier(id), depth) in assign_ids.into_iter().zip(depths) {
-            bindings.add(id, GeneratorEnum::Nested(NestedGenerator::new(depth, value_tree.clone())))?;
+            let gen = with_uniqueness(&id, GeneratorEnum::Nested(NestedGenerator::new(depth, value_tree.clone())), &unique);
+            bindings.add(id, gen, spans.definition.clone())?;
         }
 
         Ok(bindings)
@@ -55,10 +72,21 @@ impl Evaluate for NestedDefinition {
 }
 
 impl Evaluate for Definition {
-    fn evaluate(self, bindings: Bindings) -> Result<Bindings> {
+    fn evaluate(self, bindings: Bindings, spans: &mut DefinitionSpans) -> Result<Bindings> {
         match self {
-            Definition::Single(single) => single.evaluate(bindings),
-            Definition::Nested(nested) => nested.evaluate(bindings),
+            Definition::Single(single) => single.evaluate(bindings, spans),
+            Definition::Nested(nested) => nested.evaluate(bindings, spans),
         }
     }
 }
+
+/// The identifiers that a definition binds, as written after its `DEF`
+pub fn defined_ids(definition: &Definition) -> String {
+    match definition {
+        Definition::Single(SingleDefinition::SingleVal(SingleValDef(_, Identifier(id), _)))
+        | Definition::Single(SingleDefinition::MultiVal(MultiValDef(_, Identifier(id), _, _))) => id.clone(),
+
+        Definition::Nested(NestedDefinition(_, _, AssignIds(Names(ids)), _)) =>
+            ids.iter().map(|Identifier(id)| id).join(", "),
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Write;
+
+use chrono::format::{Item, StrftimeItems};
+
+use crate::mockagen::evaluator::model::{EvaluationError, OutValue, Result};
+
+#[derive(Debug)]
+pub enum Conversion {
+    Integer,
+    Real,
+    Hex,
+    String,
+}
+
+/// A single `%` placeholder, e.g. `%-8s` or `%+010.2f`
+#[derive(Debug)]
+pub struct Field {
+    left_align: bool,
+    zero_pad: bool,
+    plus_sign: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: Conversion,
+}
+
+#[derive(Debug)]
+pub enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// How a `format` value turns its inner value into text
+#[derive(Debug)]
+pub enum ValueFormat {
+    Printf { pattern: String, segments: Vec<Segment> },
+    Date(String),
+}
+
+impl ValueFormat {
+    pub fn printf(pattern: String) -> Result<Self> {
+        let segments = parse_printf(&pattern)
+            .ok_or_else(|| EvaluationError::InvalidFormatPattern(pattern.clone()))?;
+
+        Ok(Self::Printf { pattern, segments })
+    }
+
+    pub fn date(pattern: String) -> Result<Self> {
+        if StrftimeItems::new(&pattern).any(|item| matches!(item, Item::Error)) {
+            return Err(EvaluationError::InvalidFormatPattern(pattern));
+        }
+
+        Ok(Self::Date(pattern))
+    }
+
+    pub fn apply(&self, value: &OutValue) -> Result<String> {
+        match self {
+            Self::Printf { pattern, segments } =>
+                segments.iter()
+                    .map(|segment| match segment {
+                        Segment::Text(text) => Some(text.clone()),
+                        Segment::Field(field) => field.apply(value),
+                    })
+                    .collect::<Option<String>>()
+                    .ok_or_else(|| mismatch(pattern, value)),
+
+            Self::Date(pattern) => {
+                let mut output = String::new();
+
+                // chrono reports specifiers that don't fit the value (e.g. `%H` on a date) as a fmt error
+                let written = match value {
+                    OutValue::NaiveDate(date) => write!(output, "{}", date.format(pattern)),
+                    OutValue::DateTime(datetime, _) => write!(output, "{}", datetime.format(pattern)),
+                    _ => Err(std::fmt::Error),
+                };
+
+                written.map(|_| output)
+                    .map_err(|_| mismatch(pattern, value))
+            },
+        }
+    }
+}
+
+fn mismatch(pattern: &str, value: &OutValue) -> EvaluationError {
+    EvaluationError::FormatMismatch { pattern: pattern.to_owned(), value: value.to_string() }
+}
+
+impl Field {
+    fn apply(&self, value: &OutValue) -> Option<String> {
+        let (sign, body) = match (&self.conversion, value) {
+            (Conversion::Integer, OutValue::I64(v)) => split_sign(v.to_string()),
+            (Conversion::Integer, OutValue::F64(v)) => split_sign(format!("{}", v.round() as i64)),
+            (Conversion::Real, OutValue::I64(v)) => split_sign(format!("{:.*}", self.precision.unwrap_or(6), *v as f64)),
+            (Conversion::Real, OutValue::F64(v)) => split_sign(format!("{:.*}", self.precision.unwrap_or(6), v)),
+            (Conversion::Hex, OutValue::I64(v)) if *v >= 0 => (String::new(), format!("{v:x}")),
+            (Conversion::String, value) => {
+                let text = value.to_string();
+                let text = match self.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text,
+                };
+
+                return Some(self.pad(String::new(), text));
+            },
+            _ => return None,
+        };
+
+        let sign = match (sign.is_empty(), self.plus_sign) {
+            (true, true) => "+".to_owned(),
+            _ => sign,
+        };
+
+        Some(self.pad(sign, body))
+    }
+
+    fn pad(&self, sign: String, body: String) -> String {
+        let length = sign.chars().count() + body.chars().count();
+        let padding = self.width.saturating_sub(length);
+
+        match (self.left_align, self.zero_pad) {
+            (true, _) => format!("{sign}{body}{}", " ".repeat(padding)),
+            (false, true) => format!("{sign}{}{body}", "0".repeat(padding)),
+            (false, false) => format!("{}{sign}{body}", " ".repeat(padding)),
+        }
+    }
+}
+
+fn split_sign(number: String) -> (String, String) {
+    match number.strip_prefix('-') {
+        Some(body) => ("-".to_owned(), body.to_owned()),
+        None => (String::new(), number),
+    }
+}
+
+fn parse_printf(pattern: &str) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'%').is_some() {
+            text.push('%');
+            continue;
+        }
+
+        let mut field = Field { left_align: false, zero_pad: false, plus_sign: false, width: 0, precision: None, conversion: Conversion::String };
+
+        while let Some(flag) = chars.next_if(|c| matches!(c, '-' | '0' | '+')) {
+            match flag {
+                '-' => field.left_align = true,
+                '0' => field.zero_pad = true,
+                _ => field.plus_sign = true,
+            }
+        }
+
+        field.width = parse_number(&mut chars).unwrap_or(0);
+
+        if chars.next_if_eq(&'.').is_some() {
+            field.precision = Some(parse_number(&mut chars)?);
+        }
+
+        field.conversion = match chars.next()? {
+            'd' => Conversion::Integer,
+            'f' => Conversion::Real,
+            'x' => Conversion::Hex,
+            's' => Conversion::String,
+            _ => return None,
+        };
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+        }
+        segments.push(Segment::Field(field));
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+
+    Some(segments)
+}
+
+fn parse_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut digits = String::new();
+
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::mockagen::evaluator::model::OutValue;
+
+    use super::ValueFormat;
+
+    fn printf(pattern: &str, value: OutValue) -> String {
+        ValueFormat::printf(pattern.to_owned()).unwrap().apply(&value).unwrap()
+    }
+
+    #[test]
+    fn test_printf_patterns() {
+        assert_eq!(printf("%.2f", OutValue::F64(1.23456)), "1.23");
+        assert_eq!(printf("£%08.2f", OutValue::F64(-3.5)), "£-0003.50");
+        assert_eq!(printf("%+d%%", OutValue::I64(12)), "+12%");
+        assert_eq!(printf("%05d", OutValue::F64(41.6)), "00042");
+        assert_eq!(printf("[%-6s]", OutValue::String("abc".into())), "[abc   ]");
+        assert_eq!(printf("%.3s", OutValue::String("abcdef".into())), "abc");
+        assert_eq!(printf("0x%04x", OutValue::I64(255)), "0x00ff");
+
+        assert!(ValueFormat::printf("%q".into()).is_err());
+        assert!(ValueFormat::printf("%d".into()).unwrap().apply(&OutValue::String("x".into())).is_err());
+    }
+
+    #[test]
+    fn test_date_patterns() {
+        let date = OutValue::NaiveDate(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap());
+
+        assert_eq!(ValueFormat::date("%d/%m/%Y".into()).unwrap().apply(&date).unwrap(), "31/01/2024");
+        assert!(ValueFormat::date("%H:%M".into()).unwrap().apply(&date).is_err());
+        assert!(ValueFormat::date("%d/%m/%Y".into()).unwrap().apply(&OutValue::I64(1)).is_err());
+    }
+}
@@ -1,61 +1,117 @@
 use std::rc::Rc;
 
-use chrono::{Duration, NaiveDate};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
 use itertools::Itertools;
 use rand::{
     distributions::{Alphanumeric, DistString},
-    thread_rng, Rng,
+    Rng,
 };
+use rand_distr::{Exp, LogNormal, Normal, Poisson, Zipf};
 
 use crate::{mockagen::{
-    evaluator::model::{Context, CumulWeightedGen, EvaluationError, Result},
+    evaluator::model::{Context, CumulWeightedGen, EvaluationError, Result, TimestampFormat},
     packer::packer::{
-        AssignClause, AssignClauses, DateLiteral, HigherOrderValue, Identifier, IdentifierValue, IntegerLiteral, IntegerValue, JoinValue, LiteralValue, MatchClause, MatchClauses, MatchExpr, MatcherSet, Matchers, NestedClauses, PrimitiveValue, RealLiteral, RealValue, StringContent, StringLiteral, StringValue, TimestampDateValue, Value, ValueSet, Values, WeightedValue, WeightedValues, WildcardClause
+        ArrayValue, AssignClause, AssignClauses, CharsetAlphabet, ConcatValue, DateLiteral, DateTimeFormat, Distribution, DistributionClamp, DistributionValue, ExponentialDistribution, FormatValue, HigherOrderValue, Identifier, IdentifierValue, IntegerLiteral, IntegerUpperBound, IntegerValue, JoinSeparator, JoinValue, LengthLiteral, LiteralValue, LogNormalDistribution, MatchClause, MatchClauses, MatchExpr, MatcherSet, Matchers, NestedClauses, NormalDistribution, PoissonDistribution, PrimitiveValue, RealLiteral, RealUpperBound, RealValue, StringCharset, StringContent, StringLiteral, StringValue, TimestampDateTimeValue, TimestampDateValue, TupleElement, TupleMatcher, Value, ValueSet, Values, WeightedValue, WeightedValues, WildcardClause, ZipfDistribution
     }
 }, utils::iterator::FindOk};
 
-use super::model::{MaybeWeightedGen, OutValue, WeightedGen};
+use super::{arithmetic::ArithmeticGen, format::ValueFormat, matchers::Matcher, types::{PrimitiveType, TypeChecker, ValueType}, model::{DefinitionSpans, MaybeWeightedGen, OutValue, SourceSpan, WeightError, WeightedGen}, validation::check_weights};
 
 pub trait Generator2 {
     fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue>;
 }
 
+/// Upper bound used by `integer N ..`, chosen so that generated values fit a 32-bit signed column
+pub const DEFAULT_INTEGER_MAX: i64 = i32::MAX as i64;
+
+/// Upper bound used by `real N ..`, matching [`DEFAULT_INTEGER_MAX`]
+pub const DEFAULT_REAL_MAX: f64 = i32::MAX as f64;
+
+fn check_range<T: PartialOrd + std::fmt::Display>(from: &T, to: &T) -> Result<()> {
+    if from > to {
+        return Err(EvaluationError::InvalidRange { from: from.to_string(), to: to.to_string() });
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct DateRangeGen { after: NaiveDate, range_in_days: i64 }
 
 impl DateRangeGen {
-    fn new(from: NaiveDate, to: NaiveDate) -> Self {
-        Self {
+    fn new(from: NaiveDate, to: NaiveDate) -> Result<Self> {
+        check_range(&from, &to)?;
+
+        Ok(Self {
             range_in_days: to.signed_duration_since(from).num_days(),
             after: from
-        }
+        })
     }
 }
 
 impl Generator2 for DateRangeGen {
-    fn generate_value(&self, _: &mut Context) -> Result<OutValue> {
-        let offset = Duration::days(thread_rng().gen_range(0..=self.range_in_days));
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let offset = Duration::days(ctxt.rng().gen_range(0..=self.range_in_days));
 
         Ok(OutValue::NaiveDate(self.after + offset))
     }
 }
 
+impl From<Option<DateTimeFormat>> for TimestampFormat {
+    fn from(value: Option<DateTimeFormat>) -> Self {
+        match value {
+            None => Self::default(),
+            Some(DateTimeFormat::Unix(_)) => Self::Unix,
+            Some(DateTimeFormat::UnixMillis(_)) => Self::UnixMillis,
+            Some(DateTimeFormat::Rfc3339(_)) => Self::Rfc3339,
+            Some(DateTimeFormat::Rfc3339Millis(_)) => Self::Rfc3339Millis,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DateTimeRangeGen { after: DateTime<FixedOffset>, range_in_units: i64, format: TimestampFormat }
+
+impl DateTimeRangeGen {
+    fn new(from: DateTime<FixedOffset>, to: DateTime<FixedOffset>, format: TimestampFormat) -> Result<Self> {
+        check_range(&from, &to)?;
+
+        let range = to.signed_duration_since(from);
+        let range_in_units = match format.has_millis() {
+            true => range.num_milliseconds(),
+            false => range.num_seconds(),
+        };
+
+        Ok(Self { after: from, range_in_units, format })
+    }
+}
+
+impl Generator2 for DateTimeRangeGen {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let units = ctxt.rng().gen_range(0..=self.range_in_units);
+        let offset = match self.format.has_millis() {
+            true => Duration::milliseconds(units),
+            false => Duration::seconds(units),
+        };
+
+        Ok(OutValue::DateTime(self.after + offset, self.format))
+    }
+}
+
 #[derive(Debug)]
 pub struct IntegerRangeGen { from: i64, to: i64 }
 
 impl IntegerRangeGen {
-    fn new(from: i64, to: i64) -> Self {
-        // Rust doesn't support backwards ranges, so reverse negative inputs
-        Self {
-            from: from.min(to),
-            to: from.max(to)
-        }
+    fn new(from: i64, to: i64) -> Result<Self> {
+        check_range(&from, &to)?;
+
+        Ok(Self { from, to })
     }
 }
 
 impl Generator2 for IntegerRangeGen {
-    fn generate_value(&self, _: &mut Context) -> Result<OutValue> {
-        Ok(OutValue::I64(thread_rng().gen_range(self.from..=self.to)))
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        Ok(OutValue::I64(ctxt.rng().gen_range(self.from..=self.to)))
     }
 }
 
@@ -63,35 +119,171 @@ impl Generator2 for IntegerRangeGen {
 pub struct RealRangeGen { from: f64, to: f64 }
 
 impl RealRangeGen {
-    fn new(from: f64, to: f64) -> Self {
-        // Rust doesn't support backwards ranges, so reverse negative inputs
-        Self {
-            from: from.min(to),
-            to: from.max(to)
-        }
+    fn new(from: f64, to: f64) -> Result<Self> {
+        check_range(&from, &to)?;
+
+        Ok(Self { from, to })
     }
 }
 
 impl Generator2 for RealRangeGen {
-    fn generate_value(&self, _: &mut Context) -> Result<OutValue> {
-        Ok(OutValue::F64(thread_rng().gen_range(self.from..=self.to)))
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        Ok(OutValue::F64(ctxt.rng().gen_range(self.from..=self.to)))
+    }
+}
+
+/// Bounds that sampled values are moved inside of
+#[derive(Debug, Clone, Copy)]
+pub struct Clamp { min: f64, max: f64 }
+
+impl Clamp {
+    fn new(min: f64, max: f64) -> Result<Self> {
+        check_range(&min, &max)?;
+
+        Ok(Self { min, max })
+    }
+}
+
+#[derive(Debug)]
+pub struct Sampler<D> { distribution: D, clamp: Option<Clamp> }
+
+impl<D: rand_distr::Distribution<f64>> Sampler<D> {
+    fn new(clamp: Option<Clamp>, distribution: D) -> Self {
+        Self { distribution, clamp }
+    }
+
+    fn sample(&self, ctxt: &mut Context) -> f64 {
+        let value = self.distribution.sample(ctxt.rng());
+
+        match self.clamp {
+            Some(Clamp { min, max }) => value.clamp(min, max),
+            None => value,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RealDistributionGen<D>(Sampler<D>);
+
+impl<D: rand_distr::Distribution<f64>> Generator2 for RealDistributionGen<D> {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        Ok(OutValue::F64(self.0.sample(ctxt)))
+    }
+}
+
+/// Rounds samples to the nearest integer, after clamping them
+#[derive(Debug)]
+pub struct IntegerDistributionGen<D>(Sampler<D>);
+
+impl<D: rand_distr::Distribution<f64>> Generator2 for IntegerDistributionGen<D> {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        Ok(OutValue::I64(self.0.sample(ctxt).round() as i64))
+    }
+}
+
+fn check_parameter(distribution: &str, is_valid: bool, reason: &str) -> Result<()> {
+    if !is_valid {
+        return Err(EvaluationError::InvalidDistribution { distribution: distribution.into(), reason: reason.into() });
+    }
+
+    Ok(())
+}
+
+impl TryFrom<DistributionValue> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(DistributionValue(distribution, clamp): DistributionValue) -> Result<Self> {
+        let clamp = clamp
+            .map(|DistributionClamp(min, max)| Clamp::new(min.get(), max.get()))
+            .transpose()?;
+
+        // Parameters are checked here so that errors can be worded in terms of the language
+        match distribution {
+            Distribution::Normal(NormalDistribution(mean, std_dev)) => {
+                check_parameter("normal", std_dev.get() >= 0.0, "standard deviation must not be negative")?;
+
+                Ok(Self::Normal(RealDistributionGen(Sampler::new(clamp, Normal::new(mean.get(), std_dev.get()).unwrap()))))
+            },
+
+            Distribution::LogNormal(LogNormalDistribution(mean, std_dev)) => {
+                check_parameter("lognormal", mean.get() > 0.0, "mean must be positive")?;
+                check_parameter("lognormal", std_dev.get() >= 0.0, "standard deviation must not be negative")?;
+
+                Ok(Self::LogNormal(RealDistributionGen(Sampler::new(clamp, LogNormal::from_mean_cv(mean.get(), std_dev.get() / mean.get()).unwrap()))))
+            },
+
+            Distribution::Exponential(ExponentialDistribution(mean)) => {
+                check_parameter("exponential", mean.get() > 0.0, "mean must be positive")?;
+
+                Ok(Self::Exponential(RealDistributionGen(Sampler::new(clamp, Exp::new(1.0 / mean.get()).unwrap()))))
+            },
+
+            Distribution::Poisson(PoissonDistribution(mean)) => {
+                check_parameter("poisson", mean.get() > 0.0, "mean must be positive")?;
+
+                Ok(Self::Poisson(IntegerDistributionGen(Sampler::new(clamp, Poisson::new(mean.get()).unwrap()))))
+            },
+
+            Distribution::Zipf(ZipfDistribution(LengthLiteral(ranks), exponent)) => {
+                check_parameter("zipf", ranks >= 1, "there must be at least one rank")?;
+                check_parameter("zipf", exponent.get() >= 0.0, "exponent must not be negative")?;
+
+                Ok(Self::Zipf(IntegerDistributionGen(Sampler::new(clamp, Zipf::new(ranks as u64, exponent.get()).unwrap()))))
+            },
+        }
+    }
+}
+
+const LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+const HEX_DIGITS: &str = "0123456789abcdef";
+
+/// The characters that a generated string is drawn from
+#[derive(Debug)]
+pub enum Charset {
+    Alphanumeric,
+    Alphabet(Vec<char>),
+}
+
+impl From<Option<StringCharset>> for Charset {
+    fn from(value: Option<StringCharset>) -> Self {
+        match value {
+            None => Self::Alphanumeric,
+            Some(StringCharset::Letters(_)) => Self::Alphabet(LETTERS.chars().collect()),
+            Some(StringCharset::Digits(_)) => Self::Alphabet(DIGITS.chars().collect()),
+            Some(StringCharset::Hex(_)) => Self::Alphabet(HEX_DIGITS.chars().collect()),
+            Some(StringCharset::Alphabet(CharsetAlphabet(StringLiteral(StringContent(alphabet))))) =>
+                Self::Alphabet(alphabet.chars().unique().collect()),
+        }
     }
 }
 
 #[derive(Debug)]
-pub struct StringRangeGen { from: i64, to: i64 }
+pub struct StringRangeGen { from: usize, to: usize, charset: Charset }
 
 impl StringRangeGen {
-    fn new(from: i64, to: i64) -> Self {
-        Self { from, to }
+    fn new(from: usize, to: usize, charset: Charset) -> Result<Self> {
+        check_range(&from, &to)?;
+
+        Ok(Self { from, to, charset })
     }
 }
 
 impl Generator2 for StringRangeGen {
-    fn generate_value(&self, _: &mut Context) -> Result<OutValue> {
-        let length = thread_rng().gen_range(self.from..=self.to) as usize;
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let length = ctxt.rng().gen_range(self.from..=self.to);
 
-        Ok(OutValue::String(Alphanumeric.sample_string(&mut thread_rng(), length)))
+        let output = match &self.charset {
+            Charset::Alphanumeric => Alphanumeric.sample_string(ctxt.rng(), length),
+
+            Charset::Alphabet(chars) if chars.is_empty() => return Err(EvaluationError::EmptyAlphabet),
+
+            Charset::Alphabet(chars) => (0..length)
+                .map(|_| chars[ctxt.rng().gen_range(0..chars.len())])
+                .collect(),
+        };
+
+        Ok(OutValue::String(output))
     }
 }
 
@@ -127,28 +319,88 @@ impl Generator2 for IdentifierGen {
     }
 }
 
+fn generators_from(values: Vec<Value>) -> Result<Vec<GeneratorEnum>> {
+    values.into_iter()
+        .map(GeneratorEnum::try_from)
+        .collect()
+}
+
+fn generate_strings(generators: &[GeneratorEnum], ctxt: &mut Context) -> Result<Vec<String>> {
+    generators.iter()
+        .map(|gen| gen.generate_value(ctxt))
+        .map_ok(|v| v.to_string())
+        .collect()
+}
+
 #[derive(Debug)]
-pub struct JoinGen(Vec<GeneratorEnum>);
+pub struct JoinGen { separator: String, values: Vec<GeneratorEnum> }
 
 impl JoinGen {
-    fn new(values: Vec<Value>) -> Self {
-        let generators = values
-            .into_iter()
-            .map(GeneratorEnum::from)
-            .collect();
-
-        Self(generators)
+    fn new(separator: String, values: Vec<Value>) -> Result<Self> {
+        Ok(Self { separator, values: generators_from(values)? })
     }
 }
 
 impl Generator2 for JoinGen {
     fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
-        let output = self.0.iter()
+        let parts = generate_strings(&self.values, ctxt)?;
+
+        Ok(OutValue::String(parts.join(&self.separator)))
+    }
+}
+
+/// Like a join without a separator, except that a single value is passed through with its type intact
+#[derive(Debug)]
+pub struct ConcatGen(Vec<GeneratorEnum>);
+
+impl ConcatGen {
+    fn new(values: Vec<Value>) -> Result<Self> {
+        Ok(Self(generators_from(values)?))
+    }
+}
+
+impl Generator2 for ConcatGen {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        match self.0.as_slice() {
+            [ single ] => single.generate_value(ctxt),
+            values => Ok(OutValue::String(generate_strings(values, ctxt)?.concat())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ArrayGen(Vec<GeneratorEnum>);
+
+impl ArrayGen {
+    fn new(values: Vec<Value>) -> Result<Self> {
+        Ok(Self(generators_from(values)?))
+    }
+}
+
+impl Generator2 for ArrayGen {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let values = self.0.iter()
             .map(|gen| gen.generate_value(ctxt))
-            .map_ok(|v| v.to_string()) // TODO benchmark this against creating a vector and calling join on it
             .collect::<Result<_>>()?;
 
-        Ok(OutValue::String(output))
+        Ok(OutValue::List(values))
+    }
+}
+
+#[derive(Debug)]
+pub struct FormatGen { format: ValueFormat, value: Box<GeneratorEnum> }
+
+impl FormatGen {
+    fn new(format: ValueFormat, value: Value) -> Result<Self> {
+        Ok(Self { format, value: Box::new(value.try_into()?) })
+    }
+}
+
+impl Generator2 for FormatGen {
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let value = self.value.generate_value(ctxt)?;
+
+        Ok(OutValue::String(self.format.apply(&value)?))
     }
 }
 
@@ -159,14 +411,21 @@ pub struct AlternationGen {
 }
 
 impl AlternationGen {
-    fn new(maybe_weighteds: Vec<MaybeWeightedGen>) -> Self {
+    fn new(maybe_weighteds: Vec<MaybeWeightedGen>) -> Result<Self> {
+        let weights = maybe_weighteds.iter()
+            .map(|w| w.weight)
+            .collect_vec();
+
+        check_weights(&weights)?;
+
         let implicit_weighting = Self::get_implicit_weighting(maybe_weighteds.as_slice());
         let mut explicit_weighted = maybe_weighteds.into_iter()
             .map(|w| w.as_weighted_gen(implicit_weighting))
             .rev();
 
-        let WeightedGen { value: last_value, .. } = explicit_weighted.next()
-            .expect("Should have at least one value"); // TODO real error handling
+        let Some(WeightedGen { value: last_value, .. }) = explicit_weighted.next() else {
+            return Err(WeightError::Empty.into());
+        };
 
         let others = explicit_weighted
             .scan(0.0, |cumul_weight, WeightedGen { weight, value }| {
@@ -176,10 +435,10 @@ impl AlternationGen {
             })
             .collect();
 
-        Self {
+        Ok(Self {
             wgens: others,
             last: CumulWeightedGen { cumul_weight: 100.0, value: last_value }
-        }
+        })
     }
 
     fn get_implicit_weighting(maybe_weighteds: &[MaybeWeightedGen]) -> f64 {
@@ -199,7 +458,7 @@ impl AlternationGen {
 
 impl Generator2 for AlternationGen {
     fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
-        let target_weighting = thread_rng().gen_range(0.0..=100.0);
+        let target_weighting = ctxt.rng().gen_range(0.0..=100.0);
 
         self.wgens.iter()
             .find(|CumulWeightedGen { cumul_weight, .. }| target_weighting < *cumul_weight)
@@ -212,49 +471,117 @@ impl Generator2 for AlternationGen {
 
 #[derive(Debug)]
 pub enum ValueTree {
-    Match(Vec<MatchArm>, Option<Box<ValueTree>>),
+    /// Arms matching the next `width` identifiers, plus an optional wildcard arm
+    Match(usize, Vec<MatchArm>, Option<Box<ValueTree>>),
     Assign(Vec<AssignArm>, GeneratorEnum),
 }
 
 #[derive(Debug)]
 struct MatchArm {
-    match_conditions: MatchConditions,
+    ids: Vec<String>,
+    match_conditions: Vec<MatchConditions>,
     children: ValueTree,
+    span: Option<SourceSpan>,
 }
 
 impl MatchArm {
-    fn new(clause: MatchClause, ids: &[&str]) -> Result<Self> {
+    /// Only the first `using_count` of `ids` come from `USING`, and so can be matched
+    fn new(clause: MatchClause, ids: &[&str], using_count: usize, spans: &mut DefinitionSpans) -> Result<Self> {
         let MatchClause(_, matchers, nested_clauses) = clause;
+        let span = spans.next_clause();
+
+        let width = match &matchers {
+            Matchers::TupleMatcher(TupleMatcher(elements)) => elements.len(),
+            Matchers::MatchExpr(_) | Matchers::MatcherSet(_) => 1,
+        };
+
+        // Reaching past the `USING` identifiers would match a defined identifier against its own value
+        if width > using_count {
+            return Err(EvaluationError::MatchArmTooWide { width, available: using_count });
+        }
+
+        let matched_ids = &ids[..width];
 
         Ok(Self {
-            match_conditions: MatchConditions::from_matchers(ids[0], matchers),
-            children: ValueTree::from_nested_clauses(&ids[1..], nested_clauses)?,
+            ids: matched_ids.iter().map(|id| id.to_string()).collect(),
+            match_conditions: MatchConditions::from_matchers(matched_ids, matchers)?,
+            children: ValueTree::from_nested_clauses(&ids[width..], using_count - width, nested_clauses, spans)?,
+            span,
         })
     }
 
+    fn width(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn type_errors(&self, checker: &mut TypeChecker) -> Vec<EvaluationError> {
+        let mut errors = vec![];
+
+        for MatchConditions { id, matchers } in &self.match_conditions {
+            let found = checker.type_of(id);
+
+            // Nothing is known about identifiers without values, e.g. those only matched as part of a tuple
+            if found.is_empty() {
+                continue;
+            }
+
+            for matcher in matchers.iter().filter(|matcher| !found.intersects(&matcher.accepted_type())) {
+                errors.push(EvaluationError::MismatchedMatchType {
+                    id: id.clone(),
+                    found: found.clone(),
+                    matcher: matcher.to_string(),
+                    span: self.span.clone(),
+                });
+            }
+        }
+
+        errors
+    }
+
+    /// Every position must match, although wildcard positions don't have any conditions
     pub fn is_match(&self, ctxt: &mut Context) -> Result<bool> {
-        self.match_conditions.is_match(ctxt)
+        self.match_conditions.iter()
+            .try_fold(true, |is_match, conditions| Ok(is_match && conditions.is_match(ctxt)?))
     }
 }
 
 #[derive(Debug)]
 struct MatchConditions {
     id: String,
-    matchers: Vec<MatchExpr>
+    matchers: Vec<Matcher>
 }
 
 impl MatchConditions {
-    fn from_matchers(id: &str, matchers: Matchers) -> Self {
-        let matchers = match matchers {
-            Matchers::MatchExpr(match_expr) => vec![ match_expr, ],
-            Matchers::MatcherSet(MatcherSet(match_exprs)) => match_exprs,
+    fn from_matchers(ids: &[&str], matchers: Matchers) -> Result<Vec<Self>> {
+        let match_exprs = match matchers {
+            Matchers::TupleMatcher(TupleMatcher(elements)) =>
+                elements.into_iter()
+                    .map(|element| match element {
+                        TupleElement::Wildcard(_) => None,
+                        TupleElement::MatchExpr(match_expr) => Some(vec![ match_expr, ]),
+                    })
+                    .collect(),
+
+            Matchers::MatchExpr(match_expr) => vec![ Some(vec![ match_expr, ]), ],
+            Matchers::MatcherSet(MatcherSet(match_exprs)) => vec![ Some(match_exprs), ],
         };
 
-        Self { id: id.to_owned(), matchers }
+        ids.iter()
+            .zip(match_exprs)
+            .filter_map(|(id, match_exprs)| match_exprs.map(|match_exprs| Self::from_match_exprs(id, match_exprs)))
+            .collect()
     }
 
-    fn from_match_exprs(id: &str, match_exprs: Vec<MatchExpr>) -> Self {
-        Self { id: id.to_owned(), matchers: match_exprs }
+    fn from_match_exprs(id: &str, match_exprs: Vec<MatchExpr>) -> Result<Self> {
+        let matchers = match_exprs.into_iter()
+            .map(Matcher::try_from)
+            .collect::<Result<_>>()?;
+
+        Ok(Self { id: id.to_owned(), matchers })
+    }
+
+    fn new(id: &str, matchers: Vec<Matcher>) -> Self {
+        Self { id: id.to_owned(), matchers }
     }
 
     pub fn is_match(&self, ctxt: &mut Context) -> Result<bool> {
@@ -267,18 +594,20 @@ impl MatchConditions {
 #[derive(Debug)]
 struct AssignArm {
     match_conditions: MatchConditions,
-    children: Option<ValueTree>
+    children: Option<ValueTree>,
+    span: Option<SourceSpan>,
 }
 
 impl AssignArm {
-    pub fn new(id: &str, weighted_values: &WeightedValues, children: Option<ValueTree>) -> Result<Self> {
-        let match_exprs = weighted_values.1.iter()
-            .map(MatchExpr::try_from)
+    pub fn new(id: &str, weighted_values: &WeightedValues, children: Option<ValueTree>, span: Option<SourceSpan>) -> Result<Self> {
+        let matchers = weighted_values.1.iter()
+            .map(Matcher::try_from)
             .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
-            match_conditions: MatchConditions::from_match_exprs(id, match_exprs),
+            match_conditions: MatchConditions::new(id, matchers),
             children,
+            span,
         })
     }
 
@@ -288,73 +617,205 @@ impl AssignArm {
 }
 
 impl ValueTree {
-    pub fn from_nested_clauses(ids: &[&str], nested_clauses: NestedClauses) -> Result<Self> {
+    /// Clauses take their spans from `spans` in the order that they were parsed. `ids` lists the `USING` identifiers
+    /// that are left to match, `using_count` of them, followed by the defined identifiers.
+    pub fn from_nested_clauses(ids: &[&str], using_count: usize, nested_clauses: NestedClauses, spans: &mut DefinitionSpans) -> Result<Self> {
         match nested_clauses {
-            NestedClauses::AssignClauses(clauses) => Self::from_assign_clauses(ids, clauses),
-            NestedClauses::MatchClauses(clauses) => Self::from_match_clauses(ids, clauses, None),
-            NestedClauses::MatchClausesWithWildcard(clauses) => Self::from_match_clauses(ids, clauses.0, Some(clauses.1)),
+            NestedClauses::AssignClauses(clauses) => Self::from_assign_clauses(ids, clauses, spans),
+            NestedClauses::MatchClauses(clauses) => Self::from_match_clauses(ids, using_count, clauses, None, spans),
+            NestedClauses::MatchClausesWithWildcard(clauses) => Self::from_match_clauses(ids, using_count, clauses.0, Some(clauses.1), spans),
         }
     }
 
-    fn from_match_clauses(ids: &[&str], match_clauses: MatchClauses, wildcard: Option<WildcardClause>) -> Result<Self> {
+    fn from_match_clauses(ids: &[&str], using_count: usize, match_clauses: MatchClauses, wildcard: Option<WildcardClause>, spans: &mut DefinitionSpans) -> Result<Self> {
         let arms = match_clauses.0.into_iter()
-            .map(|clause| MatchArm::new(clause, ids))
-            .collect::<Result<_>>()?;
+            .map(|clause| MatchArm::new(clause, ids, using_count, spans))
+            .collect::<Result<Vec<_>>>()?;
+
+        let width = arms.first().map(MatchArm::width).unwrap_or(1);
+        if let Some(arm) = arms.iter().find(|arm| arm.width() != width) {
+            return Err(EvaluationError::MismatchedMatchArmWidths { expected: width, found: arm.width() });
+        }
 
-        let wildcard_arm = wildcard.map(|clause|
-                Self::from_nested_clauses(&ids[1..], *clause.1).map(Box::new))
+        let wildcard_arm = wildcard.map(|clause| {
+                spans.next_clause();
+                Self::from_nested_clauses(ids.get(width..).unwrap_or_default(), using_count.saturating_sub(width), *clause.1, spans).map(Box::new)
+            })
             .transpose()?;
 
-        Ok(Self::Match(arms, wildcard_arm))
+        Ok(Self::Match(width, arms, wildcard_arm))
     }
 
-    fn from_assign_clauses(ids: &[&str], assign_clauses: AssignClauses) -> Result<Self> {
+    fn from_assign_clauses(ids: &[&str], assign_clauses: AssignClauses, spans: &mut DefinitionSpans) -> Result<Self> {
         let (arms, gens): (Vec<_>, Vec<MaybeWeightedGen>) = assign_clauses.0.into_iter()
             .map(|AssignClause(_, wvalues, maybe_children)| {
+                let span = spans.next_clause();
                 let children = maybe_children
-                    .map(|c| Self::from_assign_clauses(&ids[1..], c))
+                    .map(|c| Self::from_assign_clauses(&ids[1..], c, spans))
                     .transpose()?;
 
-                let arm = AssignArm::new(ids[0], &wvalues, children)?;
-                Ok((arm, wvalues.into()))
+                let arm = AssignArm::new(ids[0], &wvalues, children, span)?;
+                Ok((arm, wvalues.try_into()?))
             })
             .collect::<Result<Vec<(AssignArm, MaybeWeightedGen)>>>()?
             .into_iter()
             .unzip();
 
-        Ok(Self::Assign(arms, GeneratorEnum::from(gens)))
+        Ok(Self::Assign(arms, GeneratorEnum::try_from(gens)?))
     }
 
     fn get_value(&self, ctxt: &mut Context) -> Result<OutValue> {
         match self {
             ValueTree::Assign(_, gen) => gen.generate_value(ctxt),
-            ValueTree::Match(_, _) => Err(EvaluationError::ExpectedValueFoundMatcher),
+            ValueTree::Match(_, _, _) => Err(EvaluationError::ExpectedValueFoundMatcher),
+        }
+    }
+
+    /// The number of identifiers consumed by this level of the tree
+    fn width(&self) -> usize {
+        match self {
+            ValueTree::Match(width, _, _) => *width,
+            ValueTree::Assign(_, _) => 1,
         }
     }
 
     fn find_child_from_context(&self, ctxt: &mut Context) -> Result<&ValueTree> {
         match self {
-            ValueTree::Match(match_arms, sibling_set) =>
-                match_arms.iter()
-                    .find_ok(|arm| arm.is_match(ctxt))?
+            ValueTree::Match(_, match_arms, sibling_set) =>
+                match match_arms.iter().find_ok(|arm| arm.is_match(ctxt))? {
+                    Some(arm) => Ok(&arm.children),
+                    None => sibling_set.as_deref().ok_or_else(|| {
+                        let first_arm = &match_arms[0];
+                        no_match(ctxt, &first_arm.ids, &first_arm.span)
+                    }),
+                },
+
+            ValueTree::Assign(assign_arms, _) => {
+                let Some(arm) = assign_arms.iter().find_ok(|arm| arm.is_match(ctxt))? else {
+                    let first_arm = &assign_arms[0];
+                    return Err(no_match(ctxt, std::slice::from_ref(&first_arm.match_conditions.id), &first_arm.span));
+                };
+
+                match &arm.children {
+                    Some(children) => Ok(children),
+                    None => {
+                        let id = arm.match_conditions.id.clone();
+                        let value = ctxt.get_value(&id)?.to_string();
+
+                        Err(EvaluationError::NoChildrenForTree { id, value, span: arm.span.clone() })
+                    },
+                }
+            },
+        }
+    }
+
+    /// The identifiers that are matched on the way down to `read_depth`, along with those used by the values found there
+    fn dependencies_at_depth(&self, read_depth: usize) -> Vec<&str> {
+        match (self, read_depth) {
+            (ValueTree::Assign(_, gen), 0) => gen.dependencies(),
+            (ValueTree::Match(_, _, _), 0) => vec![],
+
+            (ValueTree::Match(width, arms, wildcard), _) => {
+                // Every position of a tuple is read while matching, even those past `read_depth`
+                let matched_ids = arms.iter()
+                    .flat_map(|arm| arm.ids.iter().map(String::as_str));
+
+                let children = arms.iter()
                     .map(|arm| &arm.children)
-                    .or(sibling_set.as_ref().map(|b| b.as_ref()))
-                    .ok_or(EvaluationError::NoMatchForValue),
-
-            ValueTree::Assign(assign_arms, _) =>
-                assign_arms.iter()
-                    .find_ok(|arm| arm.is_match(ctxt))?
-                    .ok_or(EvaluationError::NoMatchForValue)?
-                    .children
-                    .as_ref()
-                    .ok_or(EvaluationError::NoChildrenForTree),
+                    .chain(wildcard.as_deref())
+                    .flat_map(|child| child.dependencies_at_depth(read_depth.saturating_sub(*width)));
+
+                matched_ids.chain(children).collect()
+            },
+
+            (ValueTree::Assign(arms, _), _) => {
+                let assigned_id = arms.first().map(|arm| arm.match_conditions.id.as_str());
+
+                let children = arms.iter()
+                    .filter_map(|arm| arm.children.as_ref())
+                    .flat_map(|child| child.dependencies_at_depth(read_depth - 1));
+
+                assigned_id.into_iter().chain(children).collect()
+            },
         }
     }
 
+    /// The types of every value that could be found at `read_depth`
+    fn value_type_at_depth(&self, read_depth: usize, checker: &mut TypeChecker) -> ValueType {
+        match (self, read_depth) {
+            (ValueTree::Assign(_, gen), 0) => gen.value_type(checker),
+            (ValueTree::Match(_, _, _), 0) => ValueType::default(),
+
+            // An identifier matched as part of a tuple has no values of its own
+            (ValueTree::Match(width, _, _), _) if *width > read_depth => ValueType::default(),
+
+            (ValueTree::Match(width, arms, wildcard), _) =>
+                arms.iter()
+                    .map(|arm| &arm.children)
+                    .chain(wildcard.as_deref())
+                    .map(|child| child.value_type_at_depth(read_depth - width, checker))
+                    .collect(),
+
+            (ValueTree::Assign(arms, _), _) =>
+                arms.iter()
+                    .filter_map(|arm| arm.children.as_ref())
+                    .map(|child| child.value_type_at_depth(read_depth - 1, checker))
+                    .collect(),
+        }
+    }
+
+    /// Match arms that test an identifier against a value of the wrong type, e.g. an integer against a string
+    pub fn type_errors(&self, checker: &mut TypeChecker) -> Vec<EvaluationError> {
+        let mut errors = vec![];
+
+        match self {
+            ValueTree::Match(_, arms, wildcard) => {
+                for arm in arms {
+                    errors.extend(arm.type_errors(checker));
+                    errors.extend(arm.children.type_errors(checker));
+                }
+
+                if let Some(wildcard) = wildcard {
+                    errors.extend(wildcard.type_errors(checker));
+                }
+            },
+
+            ValueTree::Assign(arms, _) =>
+                for child in arms.iter().filter_map(|arm| arm.children.as_ref()) {
+                    errors.extend(child.type_errors(checker));
+                },
+        }
+
+        errors
+    }
+
+    /// Walks down the tree until `read_depth` identifiers have been consumed
     fn generate_value_at_depth(&self, ctxt: &mut Context, read_depth: usize) -> Result<OutValue> {
-        (0..read_depth)
-            .try_fold(self, |tree, _| tree.find_child_from_context(ctxt))?
-            .get_value(ctxt)
+        let mut tree = self;
+        let mut depth = 0;
+
+        while depth < read_depth {
+            depth += tree.width();
+            tree = tree.find_child_from_context(ctxt)?;
+        }
+
+        // Overshooting means that the identifier was matched as part of a tuple, so it has no values of its own
+        match depth == read_depth {
+            true => tree.get_value(ctxt),
+            false => Err(EvaluationError::ExpectedValueFoundMatcher),
+        }
+    }
+}
+
+/// Describes the values that no arm matched, e.g. `country = 'France', band = 'Senior'`
+fn no_match(ctxt: &mut Context, ids: &[String], span: &Option<SourceSpan>) -> EvaluationError {
+    let values = ids.iter()
+        .map(|id| Ok(format!("{id} = '{}'", ctxt.get_value(id)?)))
+        .collect::<Result<Vec<_>>>();
+
+    match values {
+        Ok(values) => EvaluationError::NoMatchForValue { values: values.join(", "), span: span.clone() },
+        Err(err) => err,
     }
 }
 
@@ -373,113 +834,611 @@ impl Generator2 for NestedGenerator {
     }
 }
 
+pub const DEFAULT_UNIQUE_RETRIES: usize = 1000;
+
+/// Generates values of an identifier that never repeat across rows
+#[derive(Debug)]
+pub struct UniqueGen { id: String, retries: usize, value: Box<GeneratorEnum> }
+
+impl UniqueGen {
+    pub fn new(id: String, retries: usize, value: GeneratorEnum) -> Self {
+        Self { id, retries, value: Box::new(value) }
+    }
+}
+
+impl Generator2 for UniqueGen {
+    /// Anything that a repeated value was generated from is generated again on the next attempt
+    fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
+        let scope = ctxt.scope().clone();
+
+        for _ in 0..=self.retries {
+            let value = self.value.generate_value(ctxt)?;
+
+            if ctxt.use_unique(&self.id, &value) {
+                return Ok(value);
+            }
+
+            ctxt.restore_scope(scope.clone());
+        }
+
+        Err(EvaluationError::UniqueValuesExhausted { attempts: self.retries + 1, used: ctxt.unique_count(&self.id) })
+    }
+}
+
 #[derive(Debug)]
 pub enum GeneratorEnum {
     DateRange(DateRangeGen),
+    DateTimeRange(DateTimeRangeGen),
     IntegerRange(IntegerRangeGen),
     RealRange(RealRangeGen),
+    Normal(RealDistributionGen<Normal<f64>>),
+    LogNormal(RealDistributionGen<LogNormal<f64>>),
+    Exponential(RealDistributionGen<Exp<f64>>),
+    Poisson(IntegerDistributionGen<Poisson<f64>>),
+    Zipf(IntegerDistributionGen<Zipf<f64>>),
     StringRange(StringRangeGen),
     Literal(LiteralGen),
     Identifier(IdentifierGen),
     Alternation(Box<AlternationGen>),
     Join(JoinGen),
+    Concat(ConcatGen),
+    Array(ArrayGen),
+    Format(FormatGen),
+    Arithmetic(ArithmeticGen),
     Nested(NestedGenerator),
+    Unique(UniqueGen),
 }
 
 impl Generator2 for GeneratorEnum {
     fn generate_value(&self, ctxt: &mut Context) -> Result<OutValue> {
         match self {
             Self::DateRange(gen) => gen.generate_value(ctxt),
+            Self::DateTimeRange(gen) => gen.generate_value(ctxt),
             Self::IntegerRange(gen) => gen.generate_value(ctxt),
             Self::RealRange(gen) => gen.generate_value(ctxt),
+            Self::Normal(gen) => gen.generate_value(ctxt),
+            Self::LogNormal(gen) => gen.generate_value(ctxt),
+            Self::Exponential(gen) => gen.generate_value(ctxt),
+            Self::Poisson(gen) => gen.generate_value(ctxt),
+            Self::Zipf(gen) => gen.generate_value(ctxt),
             Self::StringRange(gen) => gen.generate_value(ctxt),
             Self::Literal(gen) => gen.generate_value(ctxt),
             Self::Identifier(gen) => gen.generate_value(ctxt),
             Self::Alternation(gen) => gen.generate_value(ctxt),
             Self::Join(gen) => gen.generate_value(ctxt),
+            Self::Concat(gen) => gen.generate_value(ctxt),
+            Self::Array(gen) => gen.generate_value(ctxt),
+            Self::Format(gen) => gen.generate_value(ctxt),
+            Self::Arithmetic(gen) => gen.generate_value(ctxt),
             Self::Nested(gen) => gen.generate_value(ctxt),
+            Self::Unique(gen) => gen.generate_value(ctxt),
         }
     }
 }
 
-impl From<HigherOrderValue> for GeneratorEnum {
-    fn from(value: HigherOrderValue) -> Self {
+impl GeneratorEnum {
+    /// The identifiers that have to be generated before this value can be
+    pub fn dependencies(&self) -> Vec<&str> {
+        match self {
+            Self::DateRange(_)
+            | Self::DateTimeRange(_)
+            | Self::IntegerRange(_)
+            | Self::RealRange(_)
+            | Self::Normal(_)
+            | Self::LogNormal(_)
+            | Self::Exponential(_)
+            | Self::Poisson(_)
+            | Self::Zipf(_)
+            | Self::StringRange(_)
+            | Self::Literal(_) => vec![],
+
+            Self::Identifier(IdentifierGen(id)) => vec![ id.as_str() ],
+
+            Self::Alternation(gen) =>
+                gen.wgens.iter()
+                    .chain(std::iter::once(&gen.last))
+                    .flat_map(|wgen| wgen.value.dependencies())
+                    .collect(),
+
+            Self::Join(JoinGen { values: gens, .. })
+            | Self::Concat(ConcatGen(gens))
+            | Self::Array(ArrayGen(gens)) => gens.iter().flat_map(Self::dependencies).collect(),
+            Self::Format(FormatGen { value, .. }) => value.dependencies(),
+            Self::Arithmetic(ArithmeticGen(expression)) => expression.dependencies(),
+            Self::Nested(NestedGenerator { read_depth, tree }) => tree.dependencies_at_depth(*read_depth),
+            Self::Unique(UniqueGen { value, .. }) => value.dependencies(),
+        }
+    }
+
+    pub fn value_type(&self, checker: &mut TypeChecker) -> ValueType {
+        match self {
+            Self::DateRange(_) => PrimitiveType::Date.into(),
+            Self::DateTimeRange(gen) => PrimitiveType::of_timestamp(gen.format).into(),
+            Self::IntegerRange(_) | Self::Poisson(_) | Self::Zipf(_) => PrimitiveType::Integer.into(),
+            Self::RealRange(_) | Self::Normal(_) | Self::LogNormal(_) | Self::Exponential(_) => PrimitiveType::Real.into(),
+            Self::StringRange(_) | Self::Literal(_) | Self::Join(_) | Self::Format(_) => PrimitiveType::String.into(),
+            Self::Concat(ConcatGen(gens)) if gens.len() == 1 => gens[0].value_type(checker),
+            Self::Concat(_) => PrimitiveType::String.into(),
+            Self::Array(_) => PrimitiveType::List.into(),
+            Self::Identifier(IdentifierGen(id)) => checker.type_of(id),
+            Self::Arithmetic(ArithmeticGen(expression)) => expression.value_type(checker),
+
+            Self::Alternation(gen) =>
+                gen.wgens.iter()
+                    .chain(std::iter::once(&gen.last))
+                    .map(|wgen| wgen.value.value_type(checker))
+                    .collect(),
+
+            Self::Nested(NestedGenerator { read_depth, tree }) => tree.value_type_at_depth(*read_depth, checker),
+            Self::Unique(UniqueGen { value, .. }) => value.value_type(checker),
+        }
+    }
+
+    pub fn value_tree(&self) -> Option<&Rc<ValueTree>> {
+        match self {
+            Self::Nested(NestedGenerator { tree, .. }) => Some(tree),
+            Self::Unique(UniqueGen { value, .. }) => value.value_tree(),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<HigherOrderValue> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(value: HigherOrderValue) -> Result<Self> {
         match value {
-            HigherOrderValue::JoinValue(JoinValue(values)) =>
-                Self::Join(JoinGen::new(values)),
+            HigherOrderValue::ArithmeticValue(value) =>
+                Ok(Self::Arithmetic(ArithmeticGen::new(value)?)),
+
+            HigherOrderValue::JoinValue(JoinValue(separator, values)) => {
+                let separator = separator
+                    .map(|JoinSeparator(StringLiteral(StringContent(separator)))| separator)
+                    .unwrap_or_default();
+
+                Ok(Self::Join(JoinGen::new(separator, values)?))
+            },
+
+            HigherOrderValue::ConcatValue(ConcatValue(values)) =>
+                Ok(Self::Concat(ConcatGen::new(values)?)),
+
+            HigherOrderValue::ArrayValue(ArrayValue(values)) =>
+                Ok(Self::Array(ArrayGen::new(values)?)),
+
+            HigherOrderValue::FormatValue(FormatValue(is_date, StringLiteral(StringContent(pattern)), value)) => {
+                let format = match is_date {
+                    Some(_) => ValueFormat::date(pattern)?,
+                    None => ValueFormat::printf(pattern)?,
+                };
+
+                Ok(Self::Format(FormatGen::new(format, *value)?))
+            },
 
             HigherOrderValue::IdentifierValue(IdentifierValue(Identifier(identifier))) =>
-                Self::Identifier(IdentifierGen::new(identifier)),
+                Ok(Self::Identifier(IdentifierGen::new(identifier))),
         }
     }
 }
 
-impl From<PrimitiveValue> for GeneratorEnum {
-    fn from(value: PrimitiveValue) -> Self {
+impl TryFrom<PrimitiveValue> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(value: PrimitiveValue) -> Result<Self> {
         match value {
             PrimitiveValue::TimestampDate(TimestampDateValue(DateLiteral(from), DateLiteral(to))) =>
-                Self::DateRange(DateRangeGen::new(from, to)),
+                Ok(Self::DateRange(DateRangeGen::new(from, to)?)),
+
+            PrimitiveValue::TimestampDateTime(TimestampDateTimeValue(format, from, to)) =>
+                Ok(Self::DateTimeRange(DateTimeRangeGen::new(from.get(), to.get(), format.into())?)),
 
             PrimitiveValue::Literal(LiteralValue(StringLiteral(StringContent(literal)))) =>
-                Self::Literal(LiteralGen::new(literal)),
+                Ok(Self::Literal(LiteralGen::new(literal))),
+
+            PrimitiveValue::Integer(IntegerValue(IntegerLiteral(from), maybe_to)) => {
+                let to = match maybe_to {
+                    None => from,
+                    Some(IntegerUpperBound::Literal(IntegerLiteral(to))) => to,
+                    Some(IntegerUpperBound::Open(_)) => DEFAULT_INTEGER_MAX,
+                };
+
+                Ok(Self::IntegerRange(IntegerRangeGen::new(from, to)?))
+            },
+
+            PrimitiveValue::String(StringValue(charset, LengthLiteral(from), LengthLiteral(to))) =>
+                Ok(Self::StringRange(StringRangeGen::new(from, to, charset.into())?)),
 
-            PrimitiveValue::Integer(IntegerValue(IntegerLiteral(from), maybe_to)) =>
-                Self::IntegerRange(IntegerRangeGen::new(from, maybe_to.map(|IntegerLiteral(i)| i).unwrap_or(i64::MAX))),
+            PrimitiveValue::Real(RealValue(RealLiteral(from), maybe_to)) => {
+                let to = match maybe_to {
+                    None => from,
+                    Some(RealUpperBound::Literal(RealLiteral(to))) => to,
+                    Some(RealUpperBound::Open(_)) => DEFAULT_REAL_MAX,
+                };
 
-            PrimitiveValue::String(StringValue(IntegerLiteral(from), IntegerLiteral(to))) =>
-                Self::IntegerRange(IntegerRangeGen::new(from, to)),
+                Ok(Self::RealRange(RealRangeGen::new(from, to)?))
+            },
 
-            PrimitiveValue::Real(RealValue(RealLiteral(from), RealLiteral(to))) =>
-                Self::RealRange(RealRangeGen::new(from, to)),
+            PrimitiveValue::Distribution(value) => value.try_into(),
         }
     }
 }
 
-impl From<Value> for GeneratorEnum {
-    fn from(value: Value) -> Self {
+impl TryFrom<Value> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(value: Value) -> Result<Self> {
         match value {
-            Value::HigherOrder(higher_order_value) => higher_order_value.into(),
-            Value::Primitive(primitive_value) => primitive_value.into(),
+            Value::HigherOrder(higher_order_value) => higher_order_value.try_into(),
+            Value::Primitive(primitive_value) => primitive_value.try_into(),
         }
     }
 }
 
-impl From<ValueSet> for GeneratorEnum {
-    fn from(values: ValueSet) -> Self {
+impl TryFrom<ValueSet> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(values: ValueSet) -> Result<Self> {
         let wvals = values.0.into_iter()
-            .map(|v| MaybeWeightedGen {
+            .map(|v| Ok(MaybeWeightedGen {
                 weight: v.0.map(|w| w.get()),
-                value: GeneratorEnum::from(v.1)
-            })
-            .collect();
+                value: GeneratorEnum::try_from(v.1)?
+            }))
+            .collect::<Result<_>>()?;
 
-        Self::Alternation(AlternationGen::new(wvals).into())
+        Ok(Self::Alternation(AlternationGen::new(wvals)?.into()))
     }
 }
 
-impl From<Vec<MaybeWeightedGen>> for GeneratorEnum {
-    fn from(value: Vec<MaybeWeightedGen>) -> Self {
-        GeneratorEnum::Alternation(AlternationGen::new(value).into())
+impl TryFrom<Vec<MaybeWeightedGen>> for GeneratorEnum {
+    type Error = EvaluationError;
+
+    fn try_from(value: Vec<MaybeWeightedGen>) -> Result<Self> {
+        Ok(GeneratorEnum::Alternation(AlternationGen::new(value)?.into()))
     }
 }
 
-impl From<WeightedValues> for MaybeWeightedGen {
-    fn from(value: WeightedValues) -> Self {
+impl TryFrom<WeightedValues> for MaybeWeightedGen {
+    type Error = EvaluationError;
+
+    fn try_from(value: WeightedValues) -> Result<Self> {
         let WeightedValues(weight, values) = value;
 
         let weight = weight.map(|w| w.get());
 
         let maybe_weighteds = match values {
             Values::Value(value) =>
-                vec![ MaybeWeightedGen { weight: Some(100.0), value: GeneratorEnum::from(value) }, ],
+                vec![ MaybeWeightedGen { weight: Some(100.0), value: GeneratorEnum::try_from(value)? }, ],
 
             Values::ValueSet(ValueSet(wvalues)) =>
                 wvalues.into_iter()
-                    .map(|v| MaybeWeightedGen { weight: v.0.map(|w| w.get()), value: GeneratorEnum::from(v.1) })
-                    .collect(),
+                    .map(|v| Ok(MaybeWeightedGen { weight: v.0.map(|w| w.get()), value: GeneratorEnum::try_from(v.1)? }))
+                    .collect::<Result<_>>()?,
         };
-        let value = GeneratorEnum::Alternation(AlternationGen::new(maybe_weighteds).into());
+        let value = GeneratorEnum::Alternation(AlternationGen::new(maybe_weighteds)?.into());
+
+        Ok(Self { weight, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use chrono::NaiveDate;
+    use itertools::Itertools;
+
+    use crate::mockagen::{
+        evaluator::model::{Bindings, Context, EvaluationError, MaybeWeightedGen, OutValue, Scope, TimestampFormat},
+        packer::packer::{
+            AssignClause, AssignClauses, DateTimeLiteral, Distribution, DistributionClamp, DistributionValue, LengthLiteral, NormalDistribution, NumberLiteral, PoissonDistribution, ZipfDistribution, IntegerLiteral, IntegerUpperBound, IntegerValue, LiteralValue, MatchClause, MatchClauses, MatchExpr, Matchers, NaiveDateTimeLiteral, NestedClauses, OpenBound, PrimitiveValue, RealLiteral, RealValue, StringContent, StringLiteral, TupleElement, TupleMatcher, TupleWildcard, UtcNumericOffset, UtcOffset, Value, ValueSet, Values, WeightedValue, WeightedValues, WildcardClause
+        },
+    };
+
+    use crate::mockagen::evaluator::{dependencies::check_dependencies, types::check_types};
+
+    use super::{ArrayGen, Charset, ConcatGen, UniqueGen, DateRangeGen, DateTimeRangeGen, Generator2, GeneratorEnum, IdentifierGen, IntegerRangeGen, JoinGen, LiteralGen, NestedGenerator, RealRangeGen, StringRangeGen, ValueTree};
+
+    fn sample(seed: u64) -> Vec<String> {
+        let alternation = GeneratorEnum::try_from(vec![
+            MaybeWeightedGen { weight: Some(30.0), value: GeneratorEnum::Literal(LiteralGen::new("a".into())) },
+            MaybeWeightedGen { weight: None, value: GeneratorEnum::Literal(LiteralGen::new("b".into())) },
+        ]).unwrap();
+
+        let generators = [
+            GeneratorEnum::IntegerRange(IntegerRangeGen::new(0, 1_000_000).unwrap()),
+            GeneratorEnum::RealRange(RealRangeGen::new(0.0, 1.0).unwrap()),
+            GeneratorEnum::StringRange(StringRangeGen::new(5, 10, Charset::Alphanumeric).unwrap()),
+            GeneratorEnum::DateRange(DateRangeGen::new(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(), NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()).unwrap()),
+            alternation,
+        ];
+
+        let mut context = Context::with_seed(Bindings::default(), seed);
+
+        (0..20)
+            .flat_map(|_| generators.iter())
+            .map(|gen| gen.generate_value(&mut context).map(|v: OutValue| v.to_string()))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible() {
+        assert_eq!(sample(42), sample(42));
+        assert_ne!(sample(42), sample(43));
+    }
+
+    #[test]
+    fn test_rows_have_fresh_scopes() {
+        let mut bindings = Bindings::default();
+        bindings.add("n".into(), GeneratorEnum::IntegerRange(IntegerRangeGen::new(0, 1_000_000).unwrap()), None).unwrap();
+        bindings.add("m".into(), GeneratorEnum::Identifier(IdentifierGen::new("n".into())), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        let rows = context.generate_rows(&["n", "m"], 5)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(rows.iter().all(|row| row[0].to_string() == row[1].to_string()));
+        assert!(rows.iter().any(|row| row[0].to_string() != rows[0][0].to_string()));
+    }
+
+    #[test]
+    fn test_rows_can_start_from_a_scope() {
+        let mut bindings = Bindings::default();
+        bindings.add("n".into(), GeneratorEnum::IntegerRange(IntegerRangeGen::new(0, 1_000_000).unwrap()), None).unwrap();
+        bindings.add("m".into(), GeneratorEnum::Identifier(IdentifierGen::new("n".into())), None).unwrap();
+        bindings.add("other".into(), GeneratorEnum::IntegerRange(IntegerRangeGen::new(0, 1_000_000).unwrap()), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        let scope = context.generate_scope_from(&Scope::default(), &["m"]).unwrap();
+        let first = context.generate_row_from(&scope, &["n", "m", "other"]).unwrap();
+        let second = context.generate_row_from(&scope, &["n", "m", "other"]).unwrap();
+
+        assert_eq!(first[0].to_string(), second[0].to_string());
+        assert_eq!(first[1].to_string(), second[1].to_string());
+        assert_ne!(first[2].to_string(), second[2].to_string());
+    }
+
+    #[test]
+    fn test_string_lengths_and_charsets() {
+        let mut context = Context::with_seed(Bindings::default(), 42);
+        let hex = StringRangeGen::new(4, 8, Charset::Alphabet("0123456789abcdef".chars().collect())).unwrap();
+
+        for _ in 0..50 {
+            let value = hex.generate_value(&mut context).unwrap().to_string();
+
+            assert!((4..=8).contains(&value.len()));
+            assert!(value.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        }
+
+        let empty = StringRangeGen::new(1, 1, Charset::Alphabet(vec![])).unwrap();
+        assert!(empty.generate_value(&mut context).is_err());
+    }
+
+    #[test]
+    fn test_integer_and_real_bounds() {
+        let mut context = Context::with_seed(Bindings::default(), 42);
+
+        let exact = GeneratorEnum::try_from(PrimitiveValue::Integer(IntegerValue(IntegerLiteral(18), None))).unwrap();
+        assert_eq!(exact.generate_value(&mut context).unwrap().to_string(), "18");
+
+        let exact = GeneratorEnum::try_from(PrimitiveValue::Real(RealValue(RealLiteral(2.5), None))).unwrap();
+        assert_eq!(exact.generate_value(&mut context).unwrap().to_string(), "2.5");
+
+        let open = GeneratorEnum::try_from(PrimitiveValue::Integer(IntegerValue(IntegerLiteral(18), Some(IntegerUpperBound::Open(OpenBound))))).unwrap();
+        for _ in 0..50 {
+            let OutValue::I64(value) = open.generate_value(&mut context).unwrap() else { panic!("expected an integer") };
+            assert!((18..=super::DEFAULT_INTEGER_MAX).contains(&value));
+        }
+
+        let backwards = PrimitiveValue::Integer(IntegerValue(IntegerLiteral(90), Some(IntegerUpperBound::Literal(IntegerLiteral(18)))));
+        assert!(matches!(GeneratorEnum::try_from(backwards), Err(EvaluationError::InvalidRange { .. })));
+        assert!(RealRangeGen::new(1.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_datetime_formats() {
+        let literal = |naive: &str, offset: Option<&str>| DateTimeLiteral(
+            NaiveDateTimeLiteral(naive.parse().unwrap()),
+            offset.map(|o| UtcOffset::Numeric(UtcNumericOffset(o.parse().unwrap()))),
+        );
+
+        let from = literal("2023-05-08T10:00:00", Some("+02:00")).get();
+        let to = literal("2023-05-08T08:00:00.250", None).get();
+        assert_eq!(to.signed_duration_since(from).num_milliseconds(), 250);
+
+        let value = |format| OutValue::DateTime(from, format);
+        assert_eq!(value(TimestampFormat::Unix).to_string(), "1683532800");
+        assert_eq!(value(TimestampFormat::UnixMillis).to_string(), "1683532800000");
+        assert_eq!(value(TimestampFormat::Rfc3339).to_string(), "2023-05-08T10:00:00+02:00");
+        assert_eq!(value(TimestampFormat::Rfc3339Millis).to_string(), "2023-05-08T10:00:00.000+02:00");
+        assert_eq!(serde_json::to_string(&value(TimestampFormat::UnixMillis)).unwrap(), "1683532800000");
+        assert_eq!(serde_json::to_string(&value(TimestampFormat::Rfc3339)).unwrap(), "\"2023-05-08T10:00:00+02:00\"");
+
+        let mut context = Context::with_seed(Bindings::default(), 42);
+        let gen = DateTimeRangeGen::new(from, to, TimestampFormat::UnixMillis).unwrap();
+        for _ in 0..50 {
+            let OutValue::DateTime(instant, _) = gen.generate_value(&mut context).unwrap() else { panic!("expected an instant") };
+            assert!(from <= instant && instant <= to);
+        }
+
+        assert!(DateTimeRangeGen::new(to, from, TimestampFormat::Unix).is_err());
+    }
+
+    fn literal(value: &str) -> LiteralValue {
+        LiteralValue(StringLiteral(StringContent(value.to_owned())))
+    }
+
+    fn tuple_clause(elements: &[Option<&str>], price: &str) -> MatchClause {
+        let elements = elements.iter()
+            .map(|element| match element {
+                Some(value) => TupleElement::MatchExpr(MatchExpr::LiteralValue(literal(value))),
+                None => TupleElement::Wildcard(TupleWildcard),
+            })
+            .collect();
+
+        MatchClause(vec![], Matchers::TupleMatcher(TupleMatcher(elements)), assign(price))
+    }
+
+    fn assign(value: &str) -> NestedClauses {
+        let value = Value::Primitive(PrimitiveValue::Literal(literal(value)));
+
+        NestedClauses::AssignClauses(AssignClauses(vec![ AssignClause(vec![], WeightedValues(None, Values::Value(value)), None) ]))
+    }
+
+    fn price_for(band: &str) -> String {
+        let clauses = MatchClauses(vec![
+            tuple_clause(&[ Some("United Kingdom"), Some("senior") ], "10"),
+            tuple_clause(&[ Some("United Kingdom"), None ], "20"),
+        ]);
+        let tree = ValueTree::from_match_clauses(&["country", "band", "price"], 2, clauses, Some(WildcardClause(vec![], Box::new(assign("30")))), &mut Default::default()).unwrap();
+
+        let mut bindings = Bindings::default();
+        bindings.add("country".into(), GeneratorEnum::Literal(LiteralGen::new("United Kingdom".into())), None).unwrap();
+        bindings.add("band".into(), GeneratorEnum::Literal(LiteralGen::new(band.into())), None).unwrap();
+        bindings.add("price".into(), GeneratorEnum::Nested(NestedGenerator::new(2, Rc::new(tree))), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        context.get_value("price").unwrap().to_string()
+    }
+
+    #[test]
+    fn test_tuple_matchers() {
+        assert_eq!(price_for("senior"), "10");
+        assert_eq!(price_for("junior"), "20");
+
+        let clauses = MatchClauses(vec![
+            tuple_clause(&[ Some("United Kingdom"), Some("senior") ], "10"),
+            MatchClause(vec![], Matchers::MatchExpr(MatchExpr::LiteralValue(literal("France"))), assign("20")),
+        ]);
+        let mismatched = ValueTree::from_match_clauses(&["country", "band", "price"], 2, clauses, None, &mut Default::default());
+        assert!(matches!(mismatched, Err(EvaluationError::MismatchedMatchArmWidths { expected: 2, found: 1 })));
+
+        // `USING country DEF a, b` can only match on `country`
+        let clauses = MatchClauses(vec![ tuple_clause(&[ Some("x"), Some("y") ], "10") ]);
+        let too_wide = ValueTree::from_match_clauses(&["country", "a", "b"], 1, clauses, None, &mut Default::default());
+        assert!(matches!(too_wide, Err(EvaluationError::MatchArmTooWide { width: 2, available: 1 })));
+    }
+
+    #[test]
+    fn test_tuple_matchers_depend_on_every_position() {
+        // A tuple that reaches past `a`'s depth still reads `a` while matching, so `a` depends on itself
+        let clauses = MatchClauses(vec![ tuple_clause(&[ Some("x"), Some("y") ], "10") ]);
+        let tree = ValueTree::from_match_clauses(&["country", "a", "b"], 2, clauses, None, &mut Default::default()).unwrap();
+
+        let mut bindings = Bindings::default();
+        bindings.add("country".into(), GeneratorEnum::Literal(LiteralGen::new("x".into())), None).unwrap();
+        bindings.add("a".into(), GeneratorEnum::Nested(NestedGenerator::new(1, Rc::new(tree))), None).unwrap();
+
+        let err = check_dependencies(&bindings).unwrap_err();
+        assert_eq!(err.to_string(), "invalid definition of 'a': dependency cycle: a -> a");
+    }
+
+    #[test]
+    fn test_no_match_names_values() {
+        let clauses = MatchClauses(vec![ tuple_clause(&[ Some("United Kingdom"), None ], "10") ]);
+        let tree = ValueTree::from_match_clauses(&["country", "band", "price"], 2, clauses, None, &mut Default::default()).unwrap();
+
+        let mut bindings = Bindings::default();
+        bindings.add("country".into(), GeneratorEnum::Literal(LiteralGen::new("France".into())), None).unwrap();
+        bindings.add("band".into(), GeneratorEnum::Literal(LiteralGen::new("senior".into())), None).unwrap();
+        bindings.add("price".into(), GeneratorEnum::Nested(NestedGenerator::new(2, Rc::new(tree))), None).unwrap();
+
+        let err = Context::with_seed(bindings, 42).get_value("price").unwrap_err();
+        assert_eq!(err.to_string(), "failed to generate 'price': no match arm for country = 'France', band = 'senior'");
+    }
+
+    #[test]
+    fn test_match_arm_types_are_checked() {
+        let integer = |n| Value::Primitive(PrimitiveValue::Integer(IntegerValue(IntegerLiteral(n), None)));
+        let clauses = || MatchClauses(vec![ MatchClause(vec![], Matchers::MatchExpr(MatchExpr::LiteralValue(literal("18"))), assign("young")) ]);
+
+        let mut bindings = Bindings::default();
+        bindings.add("age".into(), GeneratorEnum::try_from(ValueSet(vec![ WeightedValue(None, integer(18)), WeightedValue(None, Value::Primitive(PrimitiveValue::Literal(literal("unknown")))) ])).unwrap(), None).unwrap();
+        bindings.add("years".into(), GeneratorEnum::try_from(integer(18)).unwrap(), None).unwrap();
+        bindings.add("band".into(), GeneratorEnum::Nested(NestedGenerator::new(1, Rc::new(ValueTree::from_match_clauses(&["age", "band"], 1, clauses(), None, &mut Default::default()).unwrap()))), None).unwrap();
+
+        let types = check_types(&bindings).unwrap();
+        assert_eq!(types["age"].to_string(), "string | integer");
+        assert_eq!(types["band"].to_string(), "string");
+
+        bindings.add("bracket".into(), GeneratorEnum::Nested(NestedGenerator::new(1, Rc::new(ValueTree::from_match_clauses(&["years", "bracket"], 1, clauses(), None, &mut Default::default()).unwrap()))), None).unwrap();
+
+        let err = check_types(&bindings).unwrap_err();
+        assert_eq!(err.to_string(), "match arm \"18\" can never match 'years', which is of type integer");
+    }
+
+    #[test]
+    fn test_join_concat_and_array() {
+        let integer = |n| Value::Primitive(PrimitiveValue::Integer(IntegerValue(IntegerLiteral(n), None)));
+        let string = |v| Value::Primitive(PrimitiveValue::Literal(literal(v)));
+        let mut context = Context::with_seed(Bindings::default(), 42);
+
+        let join = GeneratorEnum::Join(JoinGen::new(", ".into(), vec![ string("Smith"), string("Tom") ]).unwrap());
+        assert_eq!(join.generate_value(&mut context).unwrap().to_string(), "Smith, Tom");
+
+        let single = GeneratorEnum::Concat(ConcatGen::new(vec![ integer(7) ]).unwrap());
+        assert!(matches!(single.generate_value(&mut context).unwrap(), OutValue::I64(7)));
+
+        let several = GeneratorEnum::Concat(ConcatGen::new(vec![ string("AC-"), integer(7) ]).unwrap());
+        assert!(matches!(several.generate_value(&mut context).unwrap(), OutValue::String(value) if value == "AC-7"));
+
+        let array = GeneratorEnum::Array(ArrayGen::new(vec![ string("a"), integer(7) ]).unwrap()).generate_value(&mut context).unwrap();
+        assert_eq!(serde_json::to_string(&array).unwrap(), r#"["a",7]"#);
+        assert_eq!(array.to_string(), r#"["a",7]"#);
+    }
+
+    #[test]
+    fn test_distributions() {
+        let number = |n| NumberLiteral::Integer(IntegerLiteral(n));
+        let clamp = |min, max| Some(DistributionClamp(number(min), number(max)));
+        let generate = |distribution, clamp| GeneratorEnum::try_from(DistributionValue(distribution, clamp));
+        let mut context = Context::with_seed(Bindings::default(), 42);
+
+        let age = generate(Distribution::Normal(NormalDistribution(number(40), number(30))), clamp(18, 90)).unwrap();
+        let ranks = generate(Distribution::Zipf(ZipfDistribution(LengthLiteral(10), number(1))), None).unwrap();
+        let visits = generate(Distribution::Poisson(PoissonDistribution(number(3))), None).unwrap();
+
+        for _ in 0..100 {
+            assert!(matches!(age.generate_value(&mut context).unwrap(), OutValue::F64(age) if (18.0..=90.0).contains(&age)));
+            assert!(matches!(ranks.generate_value(&mut context).unwrap(), OutValue::I64(1..=10)));
+            assert!(matches!(visits.generate_value(&mut context).unwrap(), OutValue::I64(0..)));
+        }
+
+        let err = generate(Distribution::Normal(NormalDistribution(number(40), number(-1))), None).unwrap_err();
+        assert_eq!(err.to_string(), "invalid normal distribution: standard deviation must not be negative");
+        assert!(matches!(generate(Distribution::Poisson(PoissonDistribution(number(3))), clamp(5, 1)), Err(EvaluationError::InvalidRange { .. })));
+    }
+
+    #[test]
+    fn test_unique_values_run_out() {
+        let mut bindings = Bindings::default();
+        bindings.add("id".into(), GeneratorEnum::Unique(UniqueGen::new("id".into(), 1000, GeneratorEnum::IntegerRange(IntegerRangeGen::new(1, 10).unwrap()))), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        let mut ids = context.generate_rows(&["id"], 10)
+            .map(|row| row.unwrap()[0].to_string())
+            .collect::<Vec<_>>();
+
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 10);
+
+        let err = context.generate_row(&["id"]).unwrap_err();
+        assert_eq!(err.to_string(), "failed to generate 'id': ran out of unique values after 1001 attempts (10 values already used)");
+    }
+
+    #[test]
+    fn test_unique_values_regenerate_their_inputs() {
+        let mut bindings = Bindings::default();
+        bindings.add("m".into(), GeneratorEnum::Unique(UniqueGen::new("m".into(), 1000, GeneratorEnum::Identifier(IdentifierGen::new("n".into())))), None).unwrap();
+        bindings.add("n".into(), GeneratorEnum::IntegerRange(IntegerRangeGen::new(1, 10).unwrap()), None).unwrap();
+
+        let mut context = Context::with_seed(bindings, 42);
+        let rows = context.generate_rows(&["m", "n"], 10)
+            .map(|row| row.unwrap().iter().map(ToString::to_string).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
 
-        Self { weight, value }
+        assert!(rows.iter().all(|row| row[0] == row[1]));
+        assert!(rows.iter().map(|row| &row[0]).all_unique());
     }
 }
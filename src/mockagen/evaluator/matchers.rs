@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use regex::Regex;
+
+use crate::mockagen::{
+    evaluator::{model::{EvaluationError, OutValue, Result}, types::{PrimitiveType, ValueType}},
+    packer::packer::{
+        ComparisonMatch, ComparisonOperator, DateLiteral, IntegerLiteral, LiteralValue, MatchBound, MatchExpr, PrefixMatch, PrimitiveValue, RangeMatch, RealLiteral, RegexMatch, StringContent, StringLiteral, Value
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Bound {
+    Integer(i64),
+    Real(f64),
+    Date(NaiveDate),
+    DateTime(DateTime<FixedOffset>),
+}
+
+impl Bound {
+    /// Orders `value` relative to the bound, or `None` if the two can't be compared
+    fn compare(&self, value: &OutValue) -> Option<Ordering> {
+        match (self, value) {
+            (Self::Integer(bound), OutValue::I64(value)) => Some(value.cmp(bound)),
+            (Self::Integer(bound), OutValue::F64(value)) => value.partial_cmp(&(*bound as f64)),
+            (Self::Real(bound), OutValue::I64(value)) => (*value as f64).partial_cmp(bound),
+            (Self::Real(bound), OutValue::F64(value)) => value.partial_cmp(bound),
+            (Self::Date(bound), OutValue::NaiveDate(value)) => Some(value.cmp(bound)),
+            (Self::Date(bound), OutValue::DateTime(value, _)) => Some(value.date_naive().cmp(bound)),
+            (Self::DateTime(bound), OutValue::DateTime(value, _)) => Some(value.cmp(bound)),
+            _ => None,
+        }
+    }
+
+    /// The types of value that the bound can be compared with
+    fn comparable_type(&self) -> ValueType {
+        match self {
+            Self::Integer(_) | Self::Real(_) => ValueType::of([ PrimitiveType::Integer, PrimitiveType::Real ]),
+            Self::Date(_) => ValueType::of([ PrimitiveType::Date, PrimitiveType::DateTime, PrimitiveType::UnixTimestamp ]),
+            Self::DateTime(_) => ValueType::of([ PrimitiveType::DateTime, PrimitiveType::UnixTimestamp ]),
+        }
+    }
+
+    fn to_value(&self) -> OutValue {
+        match self {
+            Self::Integer(bound) => OutValue::I64(*bound),
+            Self::Real(bound) => OutValue::F64(*bound),
+            Self::Date(bound) => OutValue::NaiveDate(*bound),
+            Self::DateTime(bound) => OutValue::DateTime(*bound, Default::default()),
+        }
+    }
+}
+
+impl From<MatchBound> for Bound {
+    fn from(value: MatchBound) -> Self {
+        match value {
+            MatchBound::Integer(IntegerLiteral(bound)) => Self::Integer(bound),
+            MatchBound::Real(RealLiteral(bound)) => Self::Real(bound),
+            MatchBound::Date(DateLiteral(bound)) => Self::Date(bound),
+            MatchBound::DateTime(literal) => Self::DateTime(literal.get()),
+        }
+    }
+}
+
+/// The condition that a match arm tests a generated value against
+#[derive(Debug)]
+pub enum Matcher {
+    Literal(String),
+    Range(Bound, Bound),
+    Comparison(Ordering, bool, Bound),
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn is_match(&self, value: &OutValue) -> bool {
+        match (self, value) {
+            (Self::Literal(expected), OutValue::String(found)) => expected == found,
+
+            (Self::Range(from, to), value) =>
+                matches!(from.compare(value), Some(Ordering::Greater | Ordering::Equal))
+                    && matches!(to.compare(value), Some(Ordering::Less | Ordering::Equal)),
+
+            (Self::Comparison(ordering, or_equal, bound), value) =>
+                match bound.compare(value) {
+                    Some(Ordering::Equal) => *or_equal,
+                    Some(found) => found == *ordering,
+                    None => false,
+                },
+
+            (Self::Prefix(prefix), OutValue::String(found)) => found.starts_with(prefix.as_str()),
+
+            (Self::Regex(regex), OutValue::String(found)) => regex.is_match(found),
+
+            _ => false,
+        }
+    }
+
+    /// The types of value that the matcher is able to match
+    pub fn accepted_type(&self) -> ValueType {
+        match self {
+            Self::Literal(_) | Self::Prefix(_) | Self::Regex(_) => PrimitiveType::String.into(),
+            Self::Range(bound, _) | Self::Comparison(_, _, bound) => bound.comparable_type(),
+        }
+    }
+}
+
+impl std::fmt::Display for Matcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Literal(literal) => f.write_fmt(format_args!("\"{literal}\"")),
+            Self::Range(from, to) => f.write_fmt(format_args!("{}..{}", from.to_value(), to.to_value())),
+            Self::Comparison(ordering, or_equal, bound) => {
+                let operator = match (ordering, or_equal) {
+                    (Ordering::Less, false) => "<",
+                    (Ordering::Less, true) => "<=",
+                    (_, false) => ">",
+                    (_, true) => ">=",
+                };
+
+                f.write_fmt(format_args!("{operator} {}", bound.to_value()))
+            },
+            Self::Prefix(prefix) => f.write_fmt(format_args!("prefix \"{prefix}\"")),
+            Self::Regex(regex) => f.write_fmt(format_args!("regex \"{regex}\"")),
+        }
+    }
+}
+
+impl TryFrom<MatchExpr> for Matcher {
+    type Error = EvaluationError;
+
+    fn try_from(value: MatchExpr) -> Result<Self> {
+        match value {
+            MatchExpr::LiteralValue(LiteralValue(StringLiteral(StringContent(literal)))) =>
+                Ok(Self::Literal(literal)),
+
+            MatchExpr::Range(RangeMatch(from, to)) => {
+                let (from, to) = (Bound::from(from), Bound::from(to));
+
+                match to.compare(&from.to_value()) {
+                    None => Err(EvaluationError::MismatchedRangeBounds { from: from.to_value().to_string(), to: to.to_value().to_string() }),
+                    Some(Ordering::Greater) => Err(EvaluationError::InvalidRange { from: from.to_value().to_string(), to: to.to_value().to_string() }),
+                    Some(_) => Ok(Self::Range(from, to)),
+                }
+            },
+
+            MatchExpr::Comparison(ComparisonMatch(operator, bound)) => {
+                let (ordering, or_equal) = match operator {
+                    ComparisonOperator::Gte(_) => (Ordering::Greater, true),
+                    ComparisonOperator::Gt(_) => (Ordering::Greater, false),
+                    ComparisonOperator::Lte(_) => (Ordering::Less, true),
+                    ComparisonOperator::Lt(_) => (Ordering::Less, false),
+                };
+
+                Ok(Self::Comparison(ordering, or_equal, bound.into()))
+            },
+
+            MatchExpr::Prefix(PrefixMatch(StringLiteral(StringContent(prefix)))) =>
+                Ok(Self::Prefix(prefix)),
+
+            MatchExpr::Regex(RegexMatch(StringLiteral(StringContent(pattern)))) =>
+                Regex::new(&pattern)
+                    .map(Self::Regex)
+                    .map_err(|err| EvaluationError::InvalidRegex { pattern, message: err.to_string() }),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Matcher {
+    type Error = EvaluationError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Primitive(PrimitiveValue::Literal(LiteralValue(StringLiteral(StringContent(literal))))) =>
+                Ok(Self::Literal(literal.to_owned())),
+
+            _ => Err(EvaluationError::InvalidMatchExprCast(value.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::mockagen::{
+        evaluator::model::OutValue,
+        packer::packer::{ComparisonMatch, ComparisonOperator, DateLiteral, IntegerLiteral, MatchBound, MatchExpr, OperatorGte, PrefixMatch, RangeMatch, RealLiteral, RegexMatch, StringContent, StringLiteral},
+    };
+
+    use super::Matcher;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn string(value: &str) -> StringLiteral {
+        StringLiteral(StringContent(value.to_owned()))
+    }
+
+    #[test]
+    fn test_range_and_comparison_matchers() {
+        let teens = Matcher::try_from(MatchExpr::Range(RangeMatch(MatchBound::Integer(IntegerLiteral(18)), MatchBound::Integer(IntegerLiteral(30))))).unwrap();
+        assert!(teens.is_match(&OutValue::I64(18)));
+        assert!(teens.is_match(&OutValue::I64(30)));
+        assert!(teens.is_match(&OutValue::F64(24.5)));
+        assert!(!teens.is_match(&OutValue::I64(31)));
+        assert!(!teens.is_match(&OutValue::String("20".into())));
+
+        let pensioners = Matcher::try_from(MatchExpr::Comparison(ComparisonMatch(ComparisonOperator::Gte(OperatorGte), MatchBound::Real(RealLiteral(65.0))))).unwrap();
+        assert!(pensioners.is_match(&OutValue::I64(65)));
+        assert!(!pensioners.is_match(&OutValue::I64(64)));
+
+        let h1 = Matcher::try_from(MatchExpr::Range(RangeMatch(MatchBound::Date(DateLiteral(date(2023, 1, 1))), MatchBound::Date(DateLiteral(date(2023, 6, 30)))))).unwrap();
+        assert!(h1.is_match(&OutValue::NaiveDate(date(2023, 6, 30))));
+        assert!(!h1.is_match(&OutValue::NaiveDate(date(2023, 7, 1))));
+
+        assert!(Matcher::try_from(MatchExpr::Range(RangeMatch(MatchBound::Integer(IntegerLiteral(30)), MatchBound::Integer(IntegerLiteral(18))))).is_err());
+        assert!(Matcher::try_from(MatchExpr::Range(RangeMatch(MatchBound::Integer(IntegerLiteral(1)), MatchBound::Date(DateLiteral(date(2023, 1, 1)))))).is_err());
+    }
+
+    #[test]
+    fn test_string_matchers() {
+        let prefix = Matcher::try_from(MatchExpr::Prefix(PrefixMatch(string("GB")))).unwrap();
+        assert!(prefix.is_match(&OutValue::String("GB1234".into())));
+        assert!(!prefix.is_match(&OutValue::String("FR1234".into())));
+
+        let regex = Matcher::try_from(MatchExpr::Regex(RegexMatch(string("^[A-Z]{2}[0-9]+$")))).unwrap();
+        assert!(regex.is_match(&OutValue::String("GB1234".into())));
+        assert!(!regex.is_match(&OutValue::String("gb1234".into())));
+
+        assert!(Matcher::try_from(MatchExpr::Regex(RegexMatch(string("(")))).is_err());
+    }
+}
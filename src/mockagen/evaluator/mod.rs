@@ -1,17 +1,33 @@
-use crate::mockagen::{evaluator::{evaluator::Evaluate, model::Bindings}, packer::packer::Body, MockagenError};
+use crate::mockagen::{evaluator::{evaluator::{defined_ids, Evaluate}, model::{Bindings, DefinitionSpans, EvaluationError}, validation::validate_weights}, packer::packer::Definition, MockagenError};
 
+mod arithmetic;
 mod generators;
+mod dependencies;
 mod evaluator;
+mod format;
+mod matchers;
+mod validation;
 pub mod model;
+pub mod types;
 pub use generators::Generator2;
+pub use dependencies::check_dependencies;
+pub use types::check_types;
 
-pub fn evaluate_mockagen(body: Body) -> Result<Bindings, MockagenError> {
-    let Body(maybe_includes, definitions, _) = body;
-
-    // maybe_includes.map(|m| m.0.iter().map(|m| m.))
-    // TODO handle includes
+/// Evaluates definitions into `bindings`. `spans` holds the source of each definition, for error messages.
+pub fn evaluate_mockagen(definitions: Vec<Definition>, spans: Vec<DefinitionSpans>, bindings: Bindings) -> Result<Bindings, MockagenError> {
+    let spans = spans.into_iter()
+        .chain(std::iter::repeat_with(DefinitionSpans::default));
 
     definitions.into_iter()
-        .try_fold(Bindings::new(), |bindings, def| def.evaluate(bindings))
+        .zip(spans)
+        .try_fold(bindings, |bindings, (def, mut spans)| {
+            let id = defined_ids(&def);
+            let definition = spans.definition.clone();
+
+            validate_weights(&def)
+                .map_err(EvaluationError::from)
+                .and_then(|_| def.evaluate(bindings, &mut spans))
+                .map_err(|err| err.in_definition(id, definition))
+        })
         .map_err(MockagenError::from)
 }
@@ -1,93 +1,485 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use chrono::NaiveDate;
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat};
+use itertools::Itertools;
+use pest::error::ErrorVariant;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use serde::{Serialize, Serializer};
 use thiserror::Error;
 
-use crate::mockagen::evaluator::generators::{Generator2, GeneratorEnum};
+use crate::mockagen::evaluator::{generators::{Generator2, GeneratorEnum}, types::ValueType};
 use crate::mockagen::packer::packer::Value;
+use crate::mockagen::parser::Rule;
 
 #[derive(Error, Debug)]
 pub enum EvaluationError {
-    #[error("duplicate identifier")]
-    DuplicateIdentifier(String),
+    #[error("duplicate identifier '{id}' (defined at {first} and {second})")]
+    DuplicateIdentifier { id: String, first: String, second: String },
 
-    #[error("unbound identifier")]
+    #[error("value for '{0}' has already been generated")]
+    DuplicateScopedValue(String),
+
+    #[error("unbound identifier '{0}'")]
     UnboundIdentifier(String),
 
+    #[error("dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    #[error("{}", .0.iter().join("\n\n"))]
+    Several(Vec<EvaluationError>),
+
     #[error("cannot cast value to match expression")]
     InvalidMatchExprCast(Value),
 
-    #[error("no match for value")]
-    NoMatchForValue, // TODO should figure out what contextual information I could add to this
+    #[error("no match arm for {values}")]
+    NoMatchForValue { values: String, span: Option<SourceSpan> },
+
+    #[error("nothing is nested under {id} = '{value}'")]
+    NoChildrenForTree { id: String, value: String, span: Option<SourceSpan> },
+
+    #[error("{}", render(.span, format!("match arm {} can never match '{}', which is of type {}", .matcher, .id, .found)))]
+    MismatchedMatchType { id: String, found: ValueType, matcher: String, span: Option<SourceSpan> },
+
+    #[error("expected a value, found match arms")]
+    ExpectedValueFoundMatcher,
+
+    #[error("string alphabet must contain at least one character")]
+    EmptyAlphabet,
+
+    #[error("invalid range: {from} is greater than {to}")]
+    InvalidRange { from: String, to: String },
+
+    #[error("ran out of unique values after {attempts} attempts ({used} values already used)")]
+    UniqueValuesExhausted { attempts: usize, used: usize },
+
+    #[error("invalid {distribution} distribution: {reason}")]
+    InvalidDistribution { distribution: String, reason: String },
 
-    #[error("no children for tree")]
-    NoChildrenForTree, // TODO should figure out what contextual information I could add to this
+    #[error("range bounds '{from}' and '{to}' have different types")]
+    MismatchedRangeBounds { from: String, to: String },
 
-    #[error("expected child, found matcher")]
-    ExpectedValueFoundMatcher, // TODO should figure out what contextual information I could add to this
+    #[error("invalid regex '{pattern}': {message}")]
+    InvalidRegex { pattern: String, message: String },
+
+    #[error("match arm covers {width} identifiers, but only {available} USING identifiers are left to match")]
+    MatchArmTooWide { width: usize, available: usize },
+
+    #[error("match arms at the same level must cover the same number of identifiers (found {expected} and {found})")]
+    MismatchedMatchArmWidths { expected: usize, found: usize },
+
+    #[error("can't calculate {0}")]
+    InvalidArithmetic(String),
+
+    #[error("'{function}' takes a value and an optional number of decimal places, but was given {found} arguments")]
+    WrongArgumentCount { function: String, found: usize },
+
+    #[error("{}", render(.span, format!("invalid definition of '{}': {}", .id, .source)))]
+    InvalidDefinition { id: String, span: Option<SourceSpan>, source: Box<EvaluationError> },
+
+    #[error("{}", render(.span, format!("failed to generate '{}': {}", .id, .source)))]
+    GenerationFailed { id: String, span: Option<SourceSpan>, source: Box<EvaluationError> },
+
+    #[error("{0}")]
+    WeightError(#[from] WeightError),
+
+    #[error("invalid format pattern '{0}'")]
+    InvalidFormatPattern(String),
+
+    #[error("cannot format '{value}' with pattern '{pattern}'")]
+    FormatMismatch { pattern: String, value: String },
 }
 
 pub type Result<T> = std::result::Result<T, EvaluationError>;
 
+impl EvaluationError {
+    /// The code that the error was raised from, for errors that know it
+    fn span(&self) -> Option<&SourceSpan> {
+        match self {
+            Self::NoMatchForValue { span, .. }
+            | Self::NoChildrenForTree { span, .. }
+            | Self::InvalidDefinition { span, .. }
+            | Self::GenerationFailed { span, .. } => span.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Names the identifier that was being generated. Errors that already name one are left alone,
+    /// as the innermost identifier is the one closest to the cause.
+    fn while_generating(self, id: &str, definition: Option<SourceSpan>) -> Self {
+        match self {
+            err @ Self::GenerationFailed { .. } => err,
+            err => Self::GenerationFailed {
+                id: id.to_owned(),
+                span: err.span().cloned().or(definition),
+                source: Box::new(err),
+            },
+        }
+    }
+
+    /// Reports every error at once, rather than only the first
+    pub fn all(mut errors: Vec<Self>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Self::Several(errors)),
+        }
+    }
+
+    pub fn in_definition(self, id: String, definition: Option<SourceSpan>) -> Self {
+        Self::InvalidDefinition { id, span: definition, source: Box::new(self) }
+    }
+}
+
+fn render(span: &Option<SourceSpan>, message: String) -> String {
+    match span {
+        Some(span) => span.render(message),
+        None => message,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WeightError {
+    #[error("there are no values to choose from")]
+    Empty,
+
+    #[error("weights add up to {0}%, which is more than 100%")]
+    Exceeds100(f64),
 
-#[derive(Debug, Default)]
-pub struct Bindings(HashMap<String, Rc<GeneratorEnum>>);
+    #[error("weights add up to {0}% rather than 100%")]
+    DoesNotSumTo100(f64),
+
+    #[error("weights add up to 100%, leaving nothing for {0} unweighted value(s)")]
+    NoneLeft(usize),
+}
+
+
+/// The source that a set of definitions was read from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Origin {
+    #[default]
+    Input,
+    File(PathBuf),
+}
+
+impl Origin {
+    /// Included paths are resolved relative to the directory of the file that includes them
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        match self {
+            Origin::Input => PathBuf::from(path),
+            Origin::File(file) => file.parent()
+                .unwrap_or(Path::new(""))
+                .join(path),
+        }
+    }
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Input => f.write_str("<input>"),
+            Origin::File(path) => f.write_fmt(format_args!("{}", path.display())),
+        }
+    }
+}
+
+/// The code of a mockagen file, shared by every span taken from it
+#[derive(Debug)]
+pub struct Source {
+    origin: Origin,
+    code: String,
+}
+
+impl Source {
+    pub fn new(origin: Origin, code: &str) -> Self {
+        Self { origin, code: code.to_owned() }
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+/// A piece of mockagen source, kept so that evaluation errors can point at the code they came from
+#[derive(Debug, Clone)]
+pub struct SourceSpan {
+    source: Rc<Source>,
+    start: usize,
+    end: usize,
+}
+
+impl SourceSpan {
+    /// Only the first line of `span` is kept, without its indentation, so that clauses aren't underlined along with everything nested in them
+    pub fn new(source: Rc<Source>, span: pest::Span<'_>) -> Self {
+        let first_line = span.as_str().lines().next().unwrap_or_default();
+        let indent = first_line.len() - first_line.trim_start().len();
+        let start = span.start() + indent;
+        let end = (span.start() + first_line.trim_end().len()).max(start);
+
+        Self { source, start, end }
+    }
+
+    fn as_pest_span(&self) -> pest::Span<'_> {
+        pest::Span::new(&self.source.code, self.start, self.end)
+            .expect("spans are taken from the source they are kept with")
+    }
+
+    /// Shows `message` beneath the spanned code, in the same style as parsing errors
+    pub fn render(&self, message: String) -> String {
+        let error = pest::error::Error::<Rule>::new_from_span(ErrorVariant::CustomError { message }, self.as_pest_span());
+
+        match &self.source.origin {
+            Origin::File(path) => error.with_path(&path.to_string_lossy()).to_string(),
+            Origin::Input => error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (line, column) = self.as_pest_span().start_pos().line_col();
+
+        f.write_fmt(format_args!("{}:{line}:{column}", self.source.origin))
+    }
+}
+
+/// Where a definition starts, along with the spans of its `?` and `=` clauses in the order they appear
+#[derive(Debug, Clone, Default)]
+pub struct DefinitionSpans {
+    pub definition: Option<SourceSpan>,
+    clauses: VecDeque<SourceSpan>,
+}
+
+impl DefinitionSpans {
+    pub fn new(definition: SourceSpan, clauses: Vec<SourceSpan>) -> Self {
+        Self { definition: Some(definition), clauses: clauses.into() }
+    }
+
+    /// Clauses are built in the same order that they were parsed, so each one takes the next span
+    pub fn next_clause(&mut self) -> Option<SourceSpan> {
+        self.clauses.pop_front()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    origin: Origin,
+    span: Option<SourceSpan>,
+    generator: Rc<GeneratorEnum>,
+}
+
+impl Binding {
+    fn location(&self) -> String {
+        match &self.span {
+            Some(span) => span.to_string(),
+            None => self.origin.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Bindings {
+    origin: Origin,
+    generators: HashMap<String, Binding>,
+    types: HashMap<String, ValueType>,
+}
 
 impl Bindings {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(origin: Origin) -> Self {
+        Self { origin, ..Default::default() }
+    }
+
+    /// Adds a generator along with the span of the definition it came from, if it came from source code
+    pub fn add(&mut self, id: String, gen: GeneratorEnum, span: Option<SourceSpan>) -> Result<Rc<GeneratorEnum>> {
+        let binding = Binding { origin: self.origin.clone(), span, generator: Rc::new(gen) };
+
+        self.insert(id, binding)
     }
 
-    pub fn add(&mut self, id: String, gen: GeneratorEnum) -> Result<Rc<GeneratorEnum>> {
-        let value = Rc::new(gen);
+    /// Moves every definition from `other` into these bindings, keeping track of where each was defined
+    pub fn merge(mut self, other: Bindings) -> Result<Self> {
+        for (id, binding) in other.generators {
+            self.insert(id, binding)?;
+        }
+
+        Ok(self)
+    }
 
-        if !self.0.contains_key(&id) {
-            self.0.insert(id, value.clone());
-            Ok(value)
-        } else {
-            Err(EvaluationError::DuplicateIdentifier(id))
+    fn insert(&mut self, id: String, binding: Binding) -> Result<Rc<GeneratorEnum>> {
+        match self.generators.entry(id) {
+            Entry::Occupied(existing) =>
+                Err(EvaluationError::DuplicateIdentifier {
+                    id: existing.key().to_owned(),
+                    first: existing.get().location(),
+                    second: binding.location(),
+                }),
+
+            Entry::Vacant(vacant) =>
+                Ok(vacant.insert(binding).generator.clone()),
         }
     }
 
     pub fn get(&self, id: &str) -> Result<Rc<GeneratorEnum>> {
-        self.0.get(id)
-            .map(Rc::clone)
+        self.generators.get(id)
+            .map(|binding| binding.generator.clone())
             .ok_or_else(|| EvaluationError::UnboundIdentifier(id.to_owned()))
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &GeneratorEnum)> {
+        self.generators.iter()
+            .map(|(id, binding)| (id.as_str(), binding.generator.as_ref()))
+    }
+
+    /// Known once the bindings have been type checked
+    pub fn type_of(&self, id: &str) -> Option<&ValueType> {
+        self.types.get(id)
+    }
+
+    pub fn set_types(&mut self, types: HashMap<String, ValueType>) {
+        self.types = types;
+    }
+
+    pub fn span_of(&self, id: &str) -> Option<SourceSpan> {
+        self.generators.get(id)
+            .and_then(|binding| binding.span.clone())
+    }
 }
 
-#[derive(Default)]
+/// The values generated so far for the current row
+#[derive(Default, Clone)]
 pub struct Scope(HashMap<String, Rc<OutValue>>);
 
 impl Scope {
-    fn get_value(&self, id: &str) -> Option<Rc<OutValue>> {
+    pub fn get_value(&self, id: &str) -> Option<Rc<OutValue>> {
         self.0.get(id).map(Rc::clone)
     }
 
+    /// Sets a value ahead of generation, replacing any that was there
+    pub fn replace_value(&mut self, id: &str, value: OutValue) {
+        self.0.insert(id.to_owned(), Rc::new(value));
+    }
+
     fn set_value(&mut self, id: &str, value: OutValue) -> Result<Rc<OutValue>> {
         let value = Rc::new(value);
 
         match self.0.insert(id.to_owned(), value.clone()) {
-            Some(_) => Err(EvaluationError::DuplicateIdentifier(id.to_owned())),
+            Some(_) => Err(EvaluationError::DuplicateScopedValue(id.to_owned())),
             None => Ok(value),
         }
     }
 }
 
+/// The values of each `UNIQUE` identifier that have been used by earlier rows
+#[derive(Default)]
+pub struct UsedValues(HashMap<String, HashSet<String>>);
+
+impl UsedValues {
+    /// Values are compared as they would be written out, so `1` and `1.0` are distinct
+    fn insert(&mut self, id: &str, value: &OutValue) -> bool {
+        self.0.entry(id.to_owned())
+            .or_default()
+            .insert(value.to_string())
+    }
+
+    fn count(&self, id: &str) -> usize {
+        self.0.get(id).map_or(0, HashSet::len)
+    }
+}
+
+/// The random number generator that every generator draws from.
+/// ChaCha is used (rather than `StdRng`) because its output is guaranteed to be the same on every platform and release,
+/// so a seed always reproduces the same data.
+pub type SeededRng = ChaCha8Rng;
+
 // TODO I suspect that Bindings should be AsRef instead of owned.
 // Actually better idea, let's assemble context from Bindings, then dismantle it into Bindings later
-pub struct Context(Bindings, Scope);
+pub struct Context(Bindings, Scope, SeededRng, UsedValues);
 
 impl Context {
+    pub fn new(bindings: Bindings, rng: SeededRng) -> Self {
+        Self(bindings, Default::default(), rng, Default::default())
+    }
+
+    pub fn with_seed(bindings: Bindings, seed: u64) -> Self {
+        Self::new(bindings, SeededRng::seed_from_u64(seed))
+    }
+
+    pub fn rng(&mut self) -> &mut SeededRng {
+        &mut self.2
+    }
+
+    /// Records a value of a `UNIQUE` identifier, returning false if an earlier row already used it
+    pub fn use_unique(&mut self, id: &str, value: &OutValue) -> bool {
+        self.3.insert(id, value)
+    }
+
+    pub fn unique_count(&self, id: &str) -> usize {
+        self.3.count(id)
+    }
+
+    /// The values generated so far for the current row
+    pub fn scope(&self) -> &Scope {
+        &self.1
+    }
+
+    /// Puts back values from earlier in the row, forgetting anything generated since
+    pub fn restore_scope(&mut self, scope: Scope) {
+        self.1 = scope;
+    }
+
+    /// Forgets every value generated so far, so that the values that follow belong to a new row
+    pub fn reset_scope(&mut self) {
+        self.1 = Scope::default();
+    }
+
+    /// Generates one value per identifier. The values within a row are consistent with one another,
+    /// e.g. a `USING country` definition will see the same country as the row's `country` column.
+    pub fn generate_row<S: AsRef<str>>(&mut self, ids: &[S]) -> Result<Vec<Rc<OutValue>>> {
+        self.reset_scope();
+
+        ids.iter()
+            .map(|id| self.get_value(id.as_ref()))
+            .collect()
+    }
+
+    /// Generates `ids` starting from the values in `scope`, returning every value that was generated along the way,
+    /// dependencies included
+    pub fn generate_scope_from<S: AsRef<str>>(&mut self, scope: &Scope, ids: &[S]) -> Result<Scope> {
+        self.1 = scope.clone();
+
+        for id in ids {
+            self.get_value(id.as_ref())?;
+        }
+
+        Ok(std::mem::take(&mut self.1))
+    }
+
+    /// Generates a row that starts from the values in `scope` rather than from nothing,
+    /// so that they and anything that depends on them stay the same as when the scope was generated
+    pub fn generate_row_from<S: AsRef<str>>(&mut self, scope: &Scope, ids: &[S]) -> Result<Vec<Rc<OutValue>>> {
+        self.1 = scope.clone();
+
+        ids.iter()
+            .map(|id| self.get_value(id.as_ref()))
+            .collect()
+    }
+
+    /// Generates `count` independent rows
+    pub fn generate_rows<'a, S: AsRef<str>>(&'a mut self, ids: &'a [S], count: usize) -> impl Iterator<Item = Result<Vec<Rc<OutValue>>>> + 'a {
+        (0..count).map(move |_| self.generate_row(ids))
+    }
+
     pub fn get_value(&mut self, id: &str) -> Result<Rc<OutValue>> {
         match self.1.get_value(id) {
             Some(scoped_value) => Ok(scoped_value),
             None => {
                 let binding = self.0.get(id)?;
-                let value = binding.generate_value(self)?;
+                let value = binding.generate_value(self)
+                    .map_err(|err| err.while_generating(id, self.0.span_of(id)))?;
 
                 self.1.set_value(id, value)
             },
@@ -97,7 +489,7 @@ impl Context {
 
 impl From<Bindings> for Context {
     fn from(bindings: Bindings) -> Self {
-        Self(bindings, Default::default())
+        Self::new(bindings, SeededRng::from_entropy())
     }
 }
 
@@ -113,6 +505,26 @@ pub enum OutValue {
     I64(i64),
     F64(f64),
     NaiveDate(NaiveDate),
+    DateTime(DateTime<FixedOffset>, TimestampFormat),
+    List(Vec<OutValue>),
+    /// Left empty in tabular outputs, e.g. an optional reference to another document
+    Null,
+}
+
+/// How a generated instant is written out, which also decides its precision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    Unix,
+    UnixMillis,
+    #[default]
+    Rfc3339,
+    Rfc3339Millis,
+}
+
+impl TimestampFormat {
+    pub fn has_millis(&self) -> bool {
+        matches!(self, Self::UnixMillis | Self::Rfc3339Millis)
+    }
 }
 
 impl std::fmt::Display for OutValue {
@@ -122,6 +534,13 @@ impl std::fmt::Display for OutValue {
             OutValue::I64(v) => f.write_fmt(format_args!("{v}")),
             OutValue::F64(v) => f.write_fmt(format_args!("{v}")),
             OutValue::NaiveDate(v) => f.write_fmt(format_args!("{}", &v.format("%Y-%m-%d"))),
+            OutValue::DateTime(v, TimestampFormat::Unix) => f.write_fmt(format_args!("{}", v.timestamp())),
+            OutValue::DateTime(v, TimestampFormat::UnixMillis) => f.write_fmt(format_args!("{}", v.timestamp_millis())),
+            OutValue::DateTime(v, TimestampFormat::Rfc3339) => f.write_str(&v.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            OutValue::DateTime(v, TimestampFormat::Rfc3339Millis) => f.write_str(&v.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            // Written as JSON, so that lists stay readable in a single CSV or TSV cell
+            OutValue::List(_) => f.write_str(&serde_json::to_string(self).map_err(|_| std::fmt::Error)?),
+            OutValue::Null => Ok(()),
         }
     }
 }
@@ -134,9 +553,14 @@ impl Serialize for OutValue {
     {
         match self {
             OutValue::NaiveDate(date) => serializer.serialize_str(&date.format("%Y-%m-%d").to_string()),
+            OutValue::DateTime(datetime, TimestampFormat::Unix) => serializer.serialize_i64(datetime.timestamp()),
+            OutValue::DateTime(datetime, TimestampFormat::UnixMillis) => serializer.serialize_i64(datetime.timestamp_millis()),
+            OutValue::DateTime(_, _) => serializer.serialize_str(&self.to_string()),
             OutValue::String(str) => serializer.serialize_str(str),
             OutValue::F64(f64) => serializer.serialize_f64(*f64),
             OutValue::I64(i64) => serializer.serialize_i64(*i64),
+            OutValue::List(values) => serializer.collect_seq(values),
+            OutValue::Null => serializer.serialize_none(),
         }
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use crate::mockagen::evaluator::{
+    generators::GeneratorEnum,
+    model::{Bindings, EvaluationError, Result, TimestampFormat},
+};
+
+/// The kinds of value that a generator can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PrimitiveType {
+    String,
+    Integer,
+    Real,
+    Date,
+    DateTime,
+    /// An instant that is written out as a number of seconds or milliseconds
+    UnixTimestamp,
+    List,
+}
+
+impl PrimitiveType {
+    pub fn of_timestamp(format: TimestampFormat) -> Self {
+        match format {
+            TimestampFormat::Unix | TimestampFormat::UnixMillis => Self::UnixTimestamp,
+            TimestampFormat::Rfc3339 | TimestampFormat::Rfc3339Millis => Self::DateTime,
+        }
+    }
+}
+
+impl std::fmt::Display for PrimitiveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Real => "real",
+            Self::Date => "date",
+            Self::DateTime => "datetime",
+            Self::UnixTimestamp => "unix timestamp",
+            Self::List => "list",
+        })
+    }
+}
+
+/// The type of an identifier's values. A `ONEOF` that mixes types has each of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValueType(BTreeSet<PrimitiveType>);
+
+impl ValueType {
+    pub fn of(types: impl IntoIterator<Item = PrimitiveType>) -> Self {
+        Self(types.into_iter().collect())
+    }
+
+    pub fn union(mut self, other: ValueType) -> Self {
+        self.0.extend(other.0);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn intersects(&self, other: &ValueType) -> bool {
+        !self.0.is_disjoint(&other.0)
+    }
+
+    pub fn primitives(&self) -> impl Iterator<Item = PrimitiveType> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl From<PrimitiveType> for ValueType {
+    fn from(value: PrimitiveType) -> Self {
+        Self::of([ value ])
+    }
+}
+
+impl FromIterator<ValueType> for ValueType {
+    fn from_iter<T: IntoIterator<Item = ValueType>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::default(), Self::union)
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.iter().join(" | "))
+    }
+}
+
+/// Infers the type of each identifier in turn, remembering those that it has already seen
+pub struct TypeChecker<'a> {
+    generators: HashMap<&'a str, &'a GeneratorEnum>,
+    types: HashMap<&'a str, ValueType>,
+}
+
+impl<'a> TypeChecker<'a> {
+    fn new(bindings: &'a Bindings) -> Self {
+        Self { generators: bindings.iter().collect(), types: HashMap::new() }
+    }
+
+    /// Relies on the bindings having no dependency cycles
+    pub fn type_of(&mut self, id: &str) -> ValueType {
+        if let Some(value_type) = self.types.get(id) {
+            return value_type.clone();
+        }
+
+        let Some((&id, generator)) = self.generators.get_key_value(id) else {
+            return ValueType::default();
+        };
+
+        let value_type = generator.value_type(self);
+        self.types.insert(id, value_type.clone());
+
+        value_type
+    }
+}
+
+/// Infers the type of every identifier, and checks that each match arm could match the identifier that it tests
+pub fn check_types(bindings: &Bindings) -> Result<HashMap<String, ValueType>> {
+    let mut checker = TypeChecker::new(bindings);
+    let mut checked_trees = HashSet::new();
+
+    // Every identifier assigned by a nested definition shares its tree, which only needs checking once
+    let errors = bindings.iter()
+        .sorted_by_key(|(id, _)| *id)
+        .filter_map(|(_, generator)| generator.value_tree())
+        .filter(|tree| checked_trees.insert(Rc::as_ptr(tree)))
+        .flat_map(|tree| tree.type_errors(&mut checker))
+        .collect_vec();
+
+    EvaluationError::all(errors)?;
+
+    Ok(bindings.iter()
+        .map(|(id, _)| (id.to_owned(), checker.type_of(id)))
+        .collect())
+}
@@ -0,0 +1,105 @@
+use itertools::Itertools;
+
+use crate::mockagen::{
+    evaluator::model::WeightError,
+    packer::packer::{
+        AssignClause, AssignClauses, Definition, MultiValDef, NestedClauses, NestedDefinition, SingleDefinition, ValueSet, Values, Weight, WeightedValue, WeightedValues
+    },
+};
+
+/// Tolerance for weights such as `8.2%` that can't be summed exactly
+const WEIGHT_EPSILON: f64 = 1e-9;
+
+/// Checks the weights of a single alternation, where `None` is a value without an explicit weight
+pub fn check_weights(weights: &[Option<f64>]) -> Result<(), WeightError> {
+    if weights.is_empty() {
+        return Err(WeightError::Empty);
+    }
+
+    let explicit_total: f64 = weights.iter().flatten().sum();
+    let implicit_count = weights.iter().filter(|weight| weight.is_none()).count();
+
+    if explicit_total > 100.0 + WEIGHT_EPSILON {
+        return Err(WeightError::Exceeds100(explicit_total));
+    }
+
+    let is_full = explicit_total > 100.0 - WEIGHT_EPSILON;
+
+    match (implicit_count, is_full) {
+        (0, false) => Err(WeightError::DoesNotSumTo100(explicit_total)),
+        (0, true) => Ok(()),
+        (_, true) => Err(WeightError::NoneLeft(implicit_count)),
+        (_, false) => Ok(()),
+    }
+}
+
+/// Checks every alternation in a definition before any generators are built from it
+pub fn validate_weights(definition: &Definition) -> Result<(), WeightError> {
+    match definition {
+        Definition::Single(SingleDefinition::SingleVal(_)) => Ok(()),
+        Definition::Single(SingleDefinition::MultiVal(MultiValDef(_, _, _, values))) => check_value_set(values),
+        Definition::Nested(NestedDefinition(_, _, _, clauses)) => check_nested_clauses(clauses),
+    }
+}
+
+fn weight_of(weight: &Option<Weight>) -> Option<f64> {
+    weight.as_ref().map(Weight::get)
+}
+
+fn check_value_set(ValueSet(values): &ValueSet) -> Result<(), WeightError> {
+    let weights = values.iter()
+        .map(|WeightedValue(weight, _)| weight_of(weight))
+        .collect_vec();
+
+    check_weights(&weights)
+}
+
+fn check_nested_clauses(clauses: &NestedClauses) -> Result<(), WeightError> {
+    match clauses {
+        NestedClauses::AssignClauses(clauses) => check_assign_clauses(clauses),
+
+        NestedClauses::MatchClauses(clauses) =>
+            clauses.0.iter().try_for_each(|clause| check_nested_clauses(&clause.2)),
+
+        NestedClauses::MatchClausesWithWildcard(clauses) => {
+            clauses.0.0.iter().try_for_each(|clause| check_nested_clauses(&clause.2))?;
+            check_nested_clauses(&clauses.1.1)
+        },
+    }
+}
+
+fn check_assign_clauses(AssignClauses(clauses): &AssignClauses) -> Result<(), WeightError> {
+    let weights = clauses.iter()
+        .map(|AssignClause(_, WeightedValues(weight, _), _)| weight_of(weight))
+        .collect_vec();
+
+    check_weights(&weights)?;
+
+    clauses.iter().try_for_each(|AssignClause(_, WeightedValues(_, values), children)| {
+        if let Values::ValueSet(value_set) = values {
+            check_value_set(value_set)?;
+        }
+
+        children.as_ref().map_or(Ok(()), check_assign_clauses)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockagen::evaluator::model::WeightError;
+
+    use super::check_weights;
+
+    #[test]
+    fn test_check_weights() {
+        assert!(check_weights(&[ Some(60.0), Some(30.0), None ]).is_ok());
+        assert!(check_weights(&[ Some(52.0), Some(3.0), Some(14.0), Some(10.0), None ]).is_ok());
+        assert!(check_weights(&[ Some(70.0), Some(30.0) ]).is_ok());
+        assert!(check_weights(&[ None, None ]).is_ok());
+
+        assert!(matches!(check_weights(&[]), Err(WeightError::Empty)));
+        assert!(matches!(check_weights(&[ Some(80.0), Some(30.0), None ]), Err(WeightError::Exceeds100(_))));
+        assert!(matches!(check_weights(&[ Some(60.0), Some(30.0) ]), Err(WeightError::DoesNotSumTo100(_))));
+        assert!(matches!(check_weights(&[ Some(60.0), Some(40.0), None ]), Err(WeightError::NoneLeft(1))));
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::mockagen::{
+    evaluator::{evaluate_mockagen, model::{Bindings, DefinitionSpans, Origin, Source, SourceSpan}},
+    packer::{pack_mockagen, packer::{Body, IncludeStatement, IncludeStatements, StringContent, StringLiteral}},
+    parser::{definition_spans, parse_mockagen},
+    MockagenError,
+};
+
+/// Loads mockagen files along with everything they `INCLUDE`.
+///
+/// Files that are reached more than once (e.g. two files including the same third file) are only evaluated once,
+/// while a file that ends up including itself is reported as a cycle.
+#[derive(Debug, Default)]
+pub struct Includes {
+    stack: Vec<PathBuf>,
+    loaded: Vec<PathBuf>,
+}
+
+impl Includes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> Result<Bindings, MockagenError> {
+        let canonical = path.canonicalize()
+            .map_err(|err| MockagenError::FileReadError { path: path.to_owned(), source: err })?;
+
+        if let Some(position) = self.stack.iter().position(|p| *p == canonical) {
+            let mut cycle = self.stack[position..].to_vec();
+            cycle.push(canonical);
+
+            return Err(MockagenError::IncludeCycle(cycle));
+        }
+
+        if self.loaded.contains(&canonical) {
+            return Ok(Bindings::new(Origin::File(path.to_owned())));
+        }
+
+        let code = std::fs::read_to_string(&canonical)
+            .map_err(|err| MockagenError::FileReadError { path: path.to_owned(), source: err })?;
+
+        self.stack.push(canonical.clone());
+        let bindings = self.load_code(&code, Origin::File(path.to_owned()));
+        self.stack.pop();
+        self.loaded.push(canonical);
+
+        bindings
+    }
+
+    pub fn load_code(&mut self, code: &str, origin: Origin) -> Result<Bindings, MockagenError> {
+        // Spans keep hold of the source, so that errors found while generating values can still show it
+        let source = Rc::new(Source::new(origin.clone(), code));
+        let pairs = parse_mockagen(source.code())
+            .map_err(|err| err.with_origin(&origin))?;
+
+        let span = |span| SourceSpan::new(source.clone(), span);
+        let spans = definition_spans(&pairs).into_iter()
+            .map(|(definition, clauses)| DefinitionSpans::new(span(definition), clauses.into_iter().map(span).collect()))
+            .collect();
+
+        let Body(maybe_includes, definitions, _) = pack_mockagen(pairs)?;
+
+        let included_paths = maybe_includes.into_iter()
+            .flat_map(|IncludeStatements(statements)| statements)
+            .flat_map(|IncludeStatement(paths)| paths)
+            .map(|StringLiteral(StringContent(path))| origin.resolve(&path))
+            .collect::<Vec<_>>();
+
+        let bindings = included_paths.iter()
+            .try_fold(Bindings::new(origin.clone()), |bindings, path| {
+                let included = self.load_file(path)?;
+
+                bindings.merge(included).map_err(MockagenError::from)
+            })?;
+
+        evaluate_mockagen(definitions, spans, bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::mockagen::{evaluator::model::EvaluationError, MockagenError};
+
+    use super::Includes;
+
+    /// Writes `files` into a fresh directory under the system's temporary directory, one per test run
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for (path, code) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, code).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_includes_resolve_relative_to_the_including_file() {
+        let dir = write_files("mocka_test_relative_includes", &[
+            ("main.mkg", "INCLUDE \"people/names.mkg\"\nDEF greeting = join \"hello \" name\n"),
+            ("people/names.mkg", "INCLUDE \"../common.mkg\"\nDEF name = join title \"Smith\"\n"),
+            ("common.mkg", "DEF title = \"Dr \"\n"),
+        ]);
+
+        let bindings = Includes::new().load_file(&dir.join("main.mkg")).unwrap();
+
+        for id in [ "greeting", "name", "title" ] {
+            assert!(bindings.get(id).is_ok(), "{id} should be bound");
+        }
+    }
+
+    #[test]
+    fn test_include_cycles_are_reported() {
+        let dir = write_files("mocka_test_include_cycles", &[
+            ("a.mkg", "INCLUDE \"b.mkg\"\nDEF a = \"a\"\n"),
+            ("b.mkg", "INCLUDE \"a.mkg\"\nDEF b = \"b\"\n"),
+        ]);
+
+        match Includes::new().load_file(&dir.join("a.mkg")) {
+            Err(MockagenError::IncludeCycle(cycle)) => {
+                let names = cycle.iter().map(|path| path.file_name().unwrap().to_string_lossy()).collect::<Vec<_>>();
+                assert_eq!(names, vec![ "a.mkg", "b.mkg", "a.mkg" ]);
+            },
+            other => panic!("expected an include cycle, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_definitions_may_not_be_repeated_across_files() {
+        let dir = write_files("mocka_test_duplicate_includes", &[
+            ("main.mkg", "INCLUDE \"smith.mkg\", \"jones.mkg\"\nDEF greeting = \"hello\"\n"),
+            ("smith.mkg", "DEF name = \"Smith\"\n"),
+            ("jones.mkg", "DEF name = \"Jones\"\n"),
+        ]);
+
+        let err = Includes::new().load_file(&dir.join("main.mkg")).unwrap_err();
+        assert!(matches!(err, MockagenError::EvaluationError(EvaluationError::DuplicateIdentifier { id, .. }) if id == "name"));
+    }
+}
@@ -1,18 +1,49 @@
-use crate::mockagen::{parser::parse_mockagen};
+use std::path::Path;
 
-use self::{evaluator::evaluate_mockagen, packer::pack_mockagen};
+use self::{evaluator::{check_dependencies, check_types, model::Origin}, includes::Includes};
 
 mod model;
 mod parser;
 mod packer;
 mod evaluator;
+mod includes;
 
 pub use model::MockagenError;
 pub use evaluator::Generator2;
-pub use evaluator::model::{OutValue, Bindings, Context};
+pub use evaluator::model::{OutValue, Bindings, Context, Scope, SeededRng};
+pub use evaluator::types::PrimitiveType;
 
+/// Evaluates mockagen code that didn't come from a file. Any includes are resolved relative to the working directory.
+#[cfg(test)]
 pub fn run_mockagen(code: &str) -> Result<Bindings, MockagenError> {
-    parse_mockagen(code)
-        .and_then(pack_mockagen)
-        .and_then(evaluate_mockagen)
+    Includes::new().load_code(code, Origin::Input)
+        .and_then(checked)
+}
+
+pub fn run_mockagen_file(path: impl AsRef<Path>) -> Result<Bindings, MockagenError> {
+    Includes::new().load_file(path.as_ref())
+        .and_then(checked)
+}
+
+/// Evaluates several files into one set of bindings, as though they were all included by the same file
+pub fn run_mockagen_files<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Bindings, MockagenError> {
+    let mut includes = Includes::new();
+
+    paths.into_iter()
+        .try_fold(Bindings::default(), |bindings, path| {
+            let loaded = includes.load_file(path.as_ref())?;
+
+            bindings.merge(loaded).map_err(MockagenError::from)
+        })
+        .and_then(checked)
+}
+
+/// References and types are only checked once every file has been loaded, as a file may use identifiers defined in the files alongside it
+fn checked(mut bindings: Bindings) -> Result<Bindings, MockagenError> {
+    check_dependencies(&bindings)?;
+
+    let types = check_types(&bindings)?;
+    bindings.set_types(types);
+
+    Ok(bindings)
 }
@@ -1,9 +1,11 @@
 use std::fmt::Debug;
+use std::path::PathBuf;
 
+use itertools::Itertools;
 use lang_packer_model::generic_utils::PackingError;
 use thiserror::Error;
 
-use super::{evaluator::model::EvaluationError, parser::Rule};
+use super::{evaluator::model::{EvaluationError, Origin}, parser::Rule};
 
 #[derive(Error, Debug)]
 pub enum MockagenError {
@@ -15,4 +17,22 @@ pub enum MockagenError {
 
     #[error("{0}")]
     EvaluationError(#[from] EvaluationError),
+
+    #[error("failed to read mockagen file '{}': {source}", .path.display())]
+    FileReadError { path: PathBuf, source: std::io::Error },
+
+    #[error("include cycle: {}", .0.iter().map(|p| p.display()).join(" -> "))]
+    IncludeCycle(Vec<PathBuf>),
+}
+
+impl MockagenError {
+    /// Attaches the file name to parsing errors, so that errors from included files can be told apart
+    pub fn with_origin(self, origin: &Origin) -> Self {
+        match (self, origin) {
+            (MockagenError::ParsingError(err), Origin::File(path)) =>
+                MockagenError::ParsingError(err.with_path(&path.to_string_lossy())),
+
+            (err, _) => err,
+        }
+    }
 }
@@ -1,9 +1,9 @@
 #![allow(dead_code)]
 
-use chrono::NaiveDate;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
 use lang_packer::Packer;
 
-use crate::mockagen::{evaluator::model::EvaluationError, parser::Rule, OutValue};
+use crate::mockagen::parser::Rule;
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::body)] // TODO nested special cases :(
@@ -19,7 +19,7 @@ pub struct IncludeStatements(pub Vec<IncludeStatement>);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::include_statement)]
-pub struct IncludeStatement;
+pub struct IncludeStatement(pub Vec<StringLiteral>);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::definition)]
@@ -37,15 +37,23 @@ pub enum SingleDefinition {
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::single_val_def)]
-pub struct SingleValDef(pub Identifier, pub Value);
+pub struct SingleValDef(pub Option<Unique>, pub Identifier, pub Value);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::multi_val_def)]
-pub struct MultiValDef(pub Identifier, pub Vec<Tab>, pub ValueSet);
+pub struct MultiValDef(pub Option<Unique>, pub Identifier, pub Vec<Tab>, pub ValueSet);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::nested_definition)]
-pub struct NestedDefinition(pub Option<UsingIds>, pub AssignIds, pub NestedClauses);
+pub struct NestedDefinition(pub Option<UsingIds>, pub Option<Unique>, pub AssignIds, pub NestedClauses);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::unique)]
+pub struct Unique(pub Option<UniqueRetries>);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::unique_retries)]
+pub struct UniqueRetries(pub LengthLiteral);
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::using_ids)]
@@ -86,10 +94,26 @@ pub struct WildcardClause(pub Vec<Tab>, pub Box<NestedClauses>);
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::matchers)]
 pub enum Matchers {
+    TupleMatcher(TupleMatcher),
     MatchExpr(MatchExpr),
     MatcherSet(MatcherSet),
 }
 
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::tuple_matcher)]
+pub struct TupleMatcher(pub Vec<TupleElement>);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::tuple_element)]
+pub enum TupleElement {
+    Wildcard(TupleWildcard),
+    MatchExpr(MatchExpr),
+}
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::tuple_wildcard)]
+pub struct TupleWildcard;
+
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::matcher_set)]
 pub struct MatcherSet(pub Vec<MatchExpr>);
@@ -97,9 +121,63 @@ pub struct MatcherSet(pub Vec<MatchExpr>);
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::match_expr)]
 pub enum MatchExpr {
+    Range(RangeMatch),
+    Comparison(ComparisonMatch),
+    Prefix(PrefixMatch),
+    Regex(RegexMatch),
     LiteralValue(LiteralValue),
 }
 
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::range_match)]
+pub struct RangeMatch(pub MatchBound, pub MatchBound);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::comparison_match)]
+pub struct ComparisonMatch(pub ComparisonOperator, pub MatchBound);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::comparison_operator)]
+pub enum ComparisonOperator {
+    Gte(OperatorGte),
+    Lte(OperatorLte),
+    Gt(OperatorGt),
+    Lt(OperatorLt),
+}
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::operator_gte)]
+pub struct OperatorGte;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::operator_lte)]
+pub struct OperatorLte;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::operator_gt)]
+pub struct OperatorGt;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::operator_lt)]
+pub struct OperatorLt;
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::match_bound)]
+pub enum MatchBound {
+    DateTime(DateTimeLiteral),
+    Date(DateLiteral),
+    Real(RealLiteral),
+    Integer(IntegerLiteral),
+}
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::prefix_match)]
+pub struct PrefixMatch(pub StringLiteral);
+
+#[derive(Debug, Packer)]
+#[packer(rule = Rule::regex_match)]
+pub struct RegexMatch(pub StringLiteral);
+
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::assign_clauses)]
 pub struct AssignClauses(pub Vec<AssignClause>);
@@ -137,43 +215,312 @@ pub enum Value {
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::higher_order_value)]
 pub enum HigherOrderValue {
+    ArithmeticValue(ArithmeticValue),
+    FormatValue(FormatValue),
     JoinValue(JoinValue),
+    ConcatValue(ConcatValue),
+    ArrayValue(ArrayValue),
     IdentifierValue(IdentifierValue),
 }
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::primitive_value)]
 pub enum PrimitiveValue {
+    TimestampDateTime(TimestampDateTimeValue),
     TimestampDate(TimestampDateValue),
     Literal(LiteralValue),
     Integer(IntegerValue),
     String(StringValue),
     Real(RealValue),
+    Distribution(DistributionValue),
 }
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::timestamp_date_value)]
 pub struct TimestampDateValue(pub DateLiteral, pub DateLiteral);
 
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::timestamp_datetime_value)]
+pub struct TimestampDateTimeValue(pub Option<DateTimeFormat>, pub DateTimeLiteral, pub DateTimeLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::datetime_format)]
+pub enum DateTimeFormat {
+    UnixMillis(FormatUnixMillis),
+    Unix(FormatUnix),
+    Rfc3339Millis(FormatRfc3339Millis),
+    Rfc3339(FormatRfc3339),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_unix_millis)]
+pub struct FormatUnixMillis;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_unix)]
+pub struct FormatUnix;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_rfc3339_millis)]
+pub struct FormatRfc3339Millis;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_rfc3339)]
+pub struct FormatRfc3339;
+
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::literal_value)]
 pub struct LiteralValue(pub StringLiteral);
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::integer_value)]
-pub struct IntegerValue(pub IntegerLiteral, pub Option<IntegerLiteral>);
+pub struct IntegerValue(pub IntegerLiteral, pub Option<IntegerUpperBound>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::integer_upper_bound)]
+pub enum IntegerUpperBound {
+    Literal(IntegerLiteral),
+    Open(OpenBound),
+}
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::string_value)]
-pub struct StringValue(pub IntegerLiteral, pub IntegerLiteral);
+pub struct StringValue(pub Option<StringCharset>, pub LengthLiteral, pub LengthLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::string_charset)]
+pub enum StringCharset {
+    Letters(CharsetLetters),
+    Digits(CharsetDigits),
+    Hex(CharsetHex),
+    Alphabet(CharsetAlphabet),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::charset_letters)]
+pub struct CharsetLetters;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::charset_digits)]
+pub struct CharsetDigits;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::charset_hex)]
+pub struct CharsetHex;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::charset_alphabet)]
+pub struct CharsetAlphabet(pub StringLiteral);
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::real_value)]
-pub struct RealValue(pub RealLiteral, pub RealLiteral);
+pub struct RealValue(pub RealLiteral, pub Option<RealUpperBound>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::real_upper_bound)]
+pub enum RealUpperBound {
+    Literal(RealLiteral),
+    Open(OpenBound),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::open_bound)]
+pub struct OpenBound;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::distribution_value)]
+pub struct DistributionValue(pub Distribution, pub Option<DistributionClamp>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::distribution)]
+pub enum Distribution {
+    Normal(NormalDistribution),
+    LogNormal(LogNormalDistribution),
+    Exponential(ExponentialDistribution),
+    Poisson(PoissonDistribution),
+    Zipf(ZipfDistribution),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::normal_distribution)]
+pub struct NormalDistribution(pub NumberLiteral, pub NumberLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::lognormal_distribution)]
+pub struct LogNormalDistribution(pub NumberLiteral, pub NumberLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::exponential_distribution)]
+pub struct ExponentialDistribution(pub NumberLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::poisson_distribution)]
+pub struct PoissonDistribution(pub NumberLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::zipf_distribution)]
+pub struct ZipfDistribution(pub LengthLiteral, pub NumberLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::distribution_clamp)]
+pub struct DistributionClamp(pub NumberLiteral, pub NumberLiteral);
 
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::join_value)]
-pub struct JoinValue(pub Vec<Value>);
+pub struct JoinValue(pub Option<JoinSeparator>, pub Vec<Value>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::join_separator)]
+pub struct JoinSeparator(pub StringLiteral);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::concat_value)]
+pub struct ConcatValue(pub Vec<Value>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::array_value)]
+pub struct ArrayValue(pub Vec<Value>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_value)]
+pub struct FormatValue(pub Option<FormatDate>, pub StringLiteral, pub Box<Value>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::format_date)]
+pub struct FormatDate;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_value)]
+pub enum ArithmeticValue {
+    Operation(ArithmeticOperation),
+    Function(ArithmeticFunction),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_operation)]
+pub struct ArithmeticOperation(pub ArithmeticOperand, pub Vec<ArithmeticStep>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_expression)]
+pub struct ArithmeticExpression(pub ArithmeticOperand, pub Vec<ArithmeticStep>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_step)]
+pub struct ArithmeticStep(pub ArithmeticOperator, pub ArithmeticOperand);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_operand)]
+pub struct ArithmeticOperand(pub ArithmeticTerm, pub Option<DurationUnit>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_term)]
+pub enum ArithmeticTerm {
+    Group(ArithmeticGroup),
+    Function(ArithmeticFunction),
+    Number(NumberLiteral),
+    Primitive(PrimitiveValue),
+    Identifier(IdentifierValue),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_group)]
+pub struct ArithmeticGroup(pub Box<ArithmeticExpression>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_function)]
+pub struct ArithmeticFunction(pub FunctionName, pub Vec<ArithmeticExpression>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_name)]
+pub enum FunctionName {
+    Min(FunctionMin),
+    Max(FunctionMax),
+    Round(FunctionRound),
+    Floor(FunctionFloor),
+    Ceil(FunctionCeil),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_min)]
+pub struct FunctionMin;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_max)]
+pub struct FunctionMax;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_round)]
+pub struct FunctionRound;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_floor)]
+pub struct FunctionFloor;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::function_ceil)]
+pub struct FunctionCeil;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::arithmetic_operator)]
+pub enum ArithmeticOperator {
+    Add(OperatorAdd),
+    Subtract(OperatorSubtract),
+    Multiply(OperatorMultiply),
+    Divide(OperatorDivide),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::operator_add)]
+pub struct OperatorAdd;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::operator_subtract)]
+pub struct OperatorSubtract;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::operator_multiply)]
+pub struct OperatorMultiply;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::operator_divide)]
+pub struct OperatorDivide;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::duration_unit)]
+pub enum DurationUnit {
+    Weeks(UnitWeeks),
+    Days(UnitDays),
+    Hours(UnitHours),
+    Minutes(UnitMinutes),
+    Seconds(UnitSeconds),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::unit_weeks)]
+pub struct UnitWeeks;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::unit_days)]
+pub struct UnitDays;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::unit_hours)]
+pub struct UnitHours;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::unit_minutes)]
+pub struct UnitMinutes;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::unit_seconds)]
+pub struct UnitSeconds;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::number_literal)]
+pub enum NumberLiteral {
+    Real(RealLiteral),
+    Integer(IntegerLiteral),
+}
 
 #[derive(Debug, Packer)]
 #[packer(rule = Rule::any_value)]
@@ -211,6 +558,29 @@ pub struct Weight(pub Weighting);
 #[packer(rule = Rule::DATE_LITERAL)]
 pub struct DateLiteral(pub NaiveDate);
 
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::DATETIME_LITERAL)]
+pub struct DateTimeLiteral(pub NaiveDateTimeLiteral, pub Option<UtcOffset>);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::NAIVE_DATETIME_LITERAL)]
+pub struct NaiveDateTimeLiteral(pub NaiveDateTime);
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::UTC_OFFSET)]
+pub enum UtcOffset {
+    Zulu(UtcZulu),
+    Numeric(UtcNumericOffset),
+}
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::UTC_ZULU)]
+pub struct UtcZulu;
+
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::UTC_NUMERIC_OFFSET)]
+pub struct UtcNumericOffset(pub FixedOffset);
+
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::REAL_LITERAL)]
 pub struct RealLiteral(pub f64);
@@ -219,6 +589,10 @@ pub struct RealLiteral(pub f64);
 #[packer(rule = Rule::INTEGER_LITERAL)]
 pub struct IntegerLiteral(pub i64);
 
+#[derive(Debug, Clone, Packer)]
+#[packer(rule = Rule::LENGTH_LITERAL)]
+pub struct LengthLiteral(pub usize);
+
 #[derive(Debug, Clone, Packer)]
 #[packer(rule = Rule::IDENTIFIER)]
 pub struct Identifier(pub String);
@@ -235,6 +609,29 @@ pub struct StringContent(pub String);
 #[packer(rule = Rule::TAB)]
 pub struct Tab;
 
+impl DateTimeLiteral {
+    /// The instant in the literal's own offset, or in UTC if it has none
+    pub fn get(&self) -> DateTime<FixedOffset> {
+        let Self(NaiveDateTimeLiteral(naive), offset) = self;
+
+        let offset = match offset {
+            Some(UtcOffset::Numeric(UtcNumericOffset(offset))) => *offset,
+            Some(UtcOffset::Zulu(_)) | None => Utc.fix(),
+        };
+
+        offset.from_local_datetime(naive).unwrap()
+    }
+}
+
+impl NumberLiteral {
+    pub fn get(&self) -> f64 {
+        match self {
+            Self::Real(RealLiteral(number)) => *number,
+            Self::Integer(IntegerLiteral(number)) => *number as f64,
+        }
+    }
+}
+
 impl Weight {
     pub fn get(&self) -> f64 {
         let percentage = match &self.0 {
@@ -251,15 +648,6 @@ impl Weight {
     }
 }
 
-impl MatchExpr {
-    pub fn is_match(&self, value: &OutValue) -> bool {
-        match (self, value) {
-            (Self::LiteralValue(LiteralValue(StringLiteral(StringContent(expected)))), OutValue::String(found)) => *expected == *found,
-            _ => false,
-        }
-    }
-}
-
 impl Values {
     pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Value> + 'a> {
         match self {
@@ -269,13 +657,3 @@ impl Values {
     }
 }
 
-impl TryFrom<&Value> for MatchExpr {
-    type Error = EvaluationError;
-
-    fn try_from(value: &Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::Primitive(PrimitiveValue::Literal(literal)) => Ok(MatchExpr::LiteralValue(literal.to_owned())),
-            _ => Err(EvaluationError::InvalidMatchExprCast(value.to_owned())),
-        }
-    }
-}
@@ -1,3 +1,3 @@
 mod parser;
 
-pub use parser::{Rule, parse_mockagen};
+pub use parser::{Rule, definition_spans, parse_mockagen};
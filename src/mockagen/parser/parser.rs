@@ -1,5 +1,5 @@
 use pest_derive::Parser;
-use pest::{iterators::Pairs, Parser};
+use pest::{iterators::Pairs, Parser, Span};
 use lang_packer_model::generic_utils::DropRules;
 
 use crate::mockagen::MockagenError;
@@ -14,8 +14,42 @@ pub fn parse_mockagen(code: &str) -> Result<Pairs<'_, Rule>, MockagenError> {
         .map_err(Into::into)
 }
 
+/// The span of each definition in the order the definitions appear, along with the spans of the `?` and `=` clauses within it
+pub fn definition_spans<'i>(pairs: &Pairs<'i, Rule>) -> Vec<(Span<'i>, Vec<Span<'i>>)> {
+    pairs.clone()
+        .flatten()
+        .filter(|pair| pair.as_rule() == Rule::definition)
+        .map(|definition| {
+            let clauses = definition.clone()
+                .into_inner()
+                .flatten()
+                .filter(|pair| matches!(pair.as_rule(), Rule::match_clause | Rule::wildcard_clause | Rule::assign_clause))
+                .map(|pair| pair.as_span())
+                .collect();
+
+            (definition.as_span(), clauses)
+        })
+        .collect()
+}
+
 impl DropRules for Rule {
     fn get_drop_rules(&self) -> Vec<Self> {
         vec![]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_mockagen;
+
+    #[test]
+    fn test_only_whole_words_are_reserved() {
+        for code in [ "DEF normal-price = integer 10\n", "DEF poisson-rate = integer 3\n", "DEF zipfian = integer 1\n", "DEF exponential-backoff = integer 2\n", "DEF formatted = integer 5\n", "DEF minimum = integer 0\n", "DEF total = join normal-price zipfian\n" ] {
+            assert!(parse_mockagen(code).is_ok(), "{code}");
+        }
+
+        for code in [ "DEF normal = integer 10\n", "DEF concat = integer 1\n", "DEF min = integer 0\n", "DEF regex = integer 1\n" ] {
+            assert!(parse_mockagen(code).is_err(), "{code}");
+        }
+    }
+}